@@ -1,2 +0,0 @@
-rm bank_database.db
-sqlite3 bank_database.db < schema.sql
@@ -1,4 +1,4 @@
-CREATE TABLE Transfer (
+CREATE TABLE IF NOT EXISTS Transfer (
 	id INTEGER PRIMARY KEY AUTOINCREMENT,
 	currency CHAR(3) NOT NULL,
 	from_account INT NOT NULL,
@@ -0,0 +1,4 @@
+CREATE TABLE UserSettings (
+	account INT PRIMARY KEY,
+	timezone VARCHAR(64)
+);
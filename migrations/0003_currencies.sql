@@ -0,0 +1,19 @@
+CREATE TABLE Currency (
+	code CHAR(3) PRIMARY KEY,
+	name VARCHAR(64) NOT NULL,
+	prefix VARCHAR(8) NOT NULL,
+	posfix VARCHAR(32) NOT NULL,
+	picture VARCHAR(255) NOT NULL,
+	subunitexp INT NOT NULL,
+	retired INT NOT NULL DEFAULT 0
+);
+
+INSERT INTO Currency (code, name, prefix, posfix, picture, subunitexp) VALUES
+	('KSN', 'Kid Server Neper', 'K$', 'nepers', 'https://media.discordapp.net/attachments/1153482364907962509/1153858790341492766/twitchiconpng1-tanglesheep.png', -2),
+	('USD', 'American Dollar', 'U$', 'dollars', 'https://media.discordapp.net/attachments/1153482364907962509/1153858888895041599/555526.png', -2),
+	('BRL', 'Brazilian Real', 'R$', 'reals', 'https://media.discordapp.net/attachments/1153482364907962509/1153858933551808605/206597.png', -2),
+	('CAD', 'Canadian Dollar', 'C$', 'dollars', 'https://cdn.discordapp.com/attachments/1153482364907962509/1153861273793081414/555473.png', -2),
+	('GBP', 'British Pound', '£', 'pounds', 'https://media.discordapp.net/attachments/1153482364907962509/1153861461182001212/555417.png', -2),
+	('EUR', 'European Euro', '€', 'euros', 'https://media.discordapp.net/attachments/1153482364907962509/1153861704141262858/330426.png', -2),
+	('BDT', 'Bangladeshi Taka', '৳', 'takas', 'https://cdn.discordapp.com/attachments/1153482364907962509/1153862592121548800/5327225.png', -2),
+	('COP', 'Colombian Peso', '$', 'pesos', 'https://media.discordapp.net/attachments/1153482364907962509/1154949125960372235/330508.png', -2);
@@ -0,0 +1,21 @@
+CREATE TABLE Account (
+	account INT NOT NULL,
+	currency CHAR(3) NOT NULL,
+	balance INT NOT NULL,
+	PRIMARY KEY (account, currency)
+);
+
+CREATE INDEX TransferFromAccount ON Transfer (currency, from_account);
+CREATE INDEX TransferToAccount ON Transfer (currency, to_account);
+
+INSERT INTO Account (account, currency, balance)
+SELECT account, currency, balance FROM (
+	SELECT account, currency, balance,
+		ROW_NUMBER() OVER (PARTITION BY account, currency ORDER BY id DESC) AS position
+	FROM (
+		SELECT id, currency, from_account AS account, from_balance AS balance FROM Transfer
+		UNION ALL
+		SELECT id, currency, to_account AS account, to_balance AS balance FROM Transfer
+	)
+)
+WHERE position = 1;
@@ -0,0 +1,2 @@
+ALTER TABLE Transfer ADD COLUMN previous_hash CHAR(64);
+ALTER TABLE Transfer ADD COLUMN hash CHAR(64);
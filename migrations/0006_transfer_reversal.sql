@@ -0,0 +1,3 @@
+ALTER TABLE Transfer ADD COLUMN reverses INT REFERENCES Transfer (id);
+
+CREATE UNIQUE INDEX TransferReverses ON Transfer (reverses);
@@ -0,0 +1,17 @@
+ALTER TABLE UserSettings ADD COLUMN confirm_threshold INT;
+
+CREATE TABLE PendingTransfer (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	currency CHAR(3) NOT NULL,
+	from_account INT NOT NULL,
+	to_account INT NOT NULL,
+	value INT NOT NULL,
+	description VARCHAR(255),
+	channel_id INT NOT NULL,
+	created_date INT NOT NULL,
+	settle_date INT NOT NULL,
+	status VARCHAR(16) NOT NULL DEFAULT 'pending',
+	transfer_id INT REFERENCES Transfer (id)
+);
+
+CREATE INDEX PendingTransferStatus ON PendingTransfer (status);
@@ -0,0 +1,15 @@
+CREATE TABLE Invoice (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	currency CHAR(3) NOT NULL,
+	requester INT NOT NULL,
+	payer INT NOT NULL,
+	value INT NOT NULL,
+	description VARCHAR(255),
+	created_date INT NOT NULL,
+	expiry_date INT NOT NULL,
+	status VARCHAR(16) NOT NULL DEFAULT 'open',
+	transfer_id INT REFERENCES Transfer (id)
+);
+
+CREATE INDEX InvoiceRequester ON Invoice (requester, status);
+CREATE INDEX InvoicePayer ON Invoice (payer, status);
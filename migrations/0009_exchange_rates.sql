@@ -0,0 +1,6 @@
+CREATE TABLE ExchangeRate (
+	currency CHAR(3) PRIMARY KEY,
+	rate INT NOT NULL,
+	source VARCHAR(64) NOT NULL,
+	updated_date INT NOT NULL
+);
@@ -0,0 +1,29 @@
+CREATE TABLE ExchangeRateHistory (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	currency CHAR(3) NOT NULL,
+	rate INT NOT NULL,
+	source VARCHAR(64) NOT NULL,
+	recorded_date INT NOT NULL
+);
+
+CREATE INDEX ExchangeRateHistoryCurrency ON ExchangeRateHistory (currency, recorded_date);
+
+INSERT INTO ExchangeRateHistory (currency, rate, source, recorded_date)
+SELECT currency, rate, source, updated_date FROM ExchangeRate;
+
+CREATE TABLE Exchange (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	account INT NOT NULL,
+	from_currency CHAR(3) NOT NULL,
+	to_currency CHAR(3) NOT NULL,
+	value INT NOT NULL,
+	credit INT NOT NULL,
+	rate INT NOT NULL,
+	spread INT NOT NULL,
+	debit_id INT NOT NULL REFERENCES Transfer (id),
+	credit_id INT NOT NULL REFERENCES Transfer (id),
+	exchange_date INT NOT NULL
+);
+
+CREATE INDEX ExchangeDebit ON Exchange (debit_id);
+CREATE INDEX ExchangeCredit ON Exchange (credit_id);
@@ -0,0 +1,86 @@
+-- Everything written before guilds existed belongs to the guild configured
+-- with BANK_DEFAULT_GUILD, which `migrate` puts in temp.MigrationSetting.
+-- Without it, a non-empty ledger fails the NOT NULL constraints below.
+CREATE TABLE Guild (
+	guild_id INT PRIMARY KEY,
+	director_id INT,
+	created_date INT NOT NULL
+);
+
+INSERT INTO Guild (guild_id, created_date)
+SELECT value, CAST(strftime('%s', 'now') AS INT) * 1000 FROM temp.MigrationSetting
+WHERE key = 'default_guild' AND value IS NOT NULL;
+
+ALTER TABLE Transfer ADD COLUMN guild_id INT NOT NULL DEFAULT 0;
+UPDATE Transfer SET guild_id = (SELECT value FROM temp.MigrationSetting WHERE key = 'default_guild');
+
+DROP INDEX TransferFromAccount;
+DROP INDEX TransferToAccount;
+CREATE INDEX TransferFromAccount ON Transfer (guild_id, currency, from_account);
+CREATE INDEX TransferToAccount ON Transfer (guild_id, currency, to_account);
+
+ALTER TABLE Account RENAME TO LegacyAccount;
+CREATE TABLE Account (
+	guild_id INT NOT NULL,
+	account INT NOT NULL,
+	currency CHAR(3) NOT NULL,
+	balance INT NOT NULL,
+	PRIMARY KEY (guild_id, account, currency)
+);
+INSERT INTO Account (guild_id, account, currency, balance)
+SELECT (SELECT value FROM temp.MigrationSetting WHERE key = 'default_guild'), account, currency, balance
+FROM LegacyAccount;
+DROP TABLE LegacyAccount;
+
+-- The currencies of guild 0 are templates copied into every new guild.
+ALTER TABLE Currency RENAME TO LegacyCurrency;
+CREATE TABLE Currency (
+	guild_id INT NOT NULL,
+	code CHAR(3) NOT NULL,
+	name VARCHAR(64) NOT NULL,
+	prefix VARCHAR(8) NOT NULL,
+	posfix VARCHAR(32) NOT NULL,
+	picture VARCHAR(255) NOT NULL,
+	subunitexp INT NOT NULL,
+	retired INT NOT NULL DEFAULT 0,
+	PRIMARY KEY (guild_id, code)
+);
+INSERT INTO Currency (guild_id, code, name, prefix, posfix, picture, subunitexp, retired)
+SELECT 0, code, name, prefix, posfix, picture, subunitexp, retired FROM LegacyCurrency ORDER BY rowid;
+INSERT INTO Currency (guild_id, code, name, prefix, posfix, picture, subunitexp, retired)
+SELECT setting.value, code, name, prefix, posfix, picture, subunitexp, retired
+FROM LegacyCurrency JOIN temp.MigrationSetting AS setting
+ON setting.key = 'default_guild' AND setting.value IS NOT NULL
+ORDER BY LegacyCurrency.rowid;
+DROP TABLE LegacyCurrency;
+
+ALTER TABLE PendingTransfer ADD COLUMN guild_id INT NOT NULL DEFAULT 0;
+UPDATE PendingTransfer SET guild_id = (SELECT value FROM temp.MigrationSetting WHERE key = 'default_guild');
+
+ALTER TABLE Invoice ADD COLUMN guild_id INT NOT NULL DEFAULT 0;
+UPDATE Invoice SET guild_id = (SELECT value FROM temp.MigrationSetting WHERE key = 'default_guild');
+
+ALTER TABLE Exchange ADD COLUMN guild_id INT NOT NULL DEFAULT 0;
+UPDATE Exchange SET guild_id = (SELECT value FROM temp.MigrationSetting WHERE key = 'default_guild');
+
+ALTER TABLE ExchangeRate RENAME TO LegacyExchangeRate;
+CREATE TABLE ExchangeRate (
+	guild_id INT NOT NULL,
+	currency CHAR(3) NOT NULL,
+	rate INT NOT NULL,
+	source VARCHAR(64) NOT NULL,
+	updated_date INT NOT NULL,
+	PRIMARY KEY (guild_id, currency)
+);
+INSERT INTO ExchangeRate (guild_id, currency, rate, source, updated_date)
+SELECT (SELECT value FROM temp.MigrationSetting WHERE key = 'default_guild'), currency, rate, source, updated_date
+FROM LegacyExchangeRate;
+DROP TABLE LegacyExchangeRate;
+
+ALTER TABLE ExchangeRateHistory ADD COLUMN guild_id INT NOT NULL DEFAULT 0;
+UPDATE ExchangeRateHistory SET guild_id = (SELECT value FROM temp.MigrationSetting WHERE key = 'default_guild');
+DROP INDEX ExchangeRateHistoryCurrency;
+CREATE INDEX ExchangeRateHistoryCurrency ON ExchangeRateHistory (guild_id, currency, recorded_date);
+
+-- Guild a user's direct messages act on.
+ALTER TABLE UserSettings ADD COLUMN dm_guild INT;
@@ -0,0 +1,22 @@
+-- Roles held by a user directly, or by everyone with a Discord role.
+CREATE TABLE RoleGrant (
+	guild_id INT NOT NULL,
+	user_id INT NOT NULL,
+	role VARCHAR(16) NOT NULL,
+	granted_date INT NOT NULL,
+	PRIMARY KEY (guild_id, user_id, role)
+);
+
+CREATE TABLE RoleMapping (
+	guild_id INT NOT NULL,
+	discord_role_id INT NOT NULL,
+	role VARCHAR(16) NOT NULL,
+	granted_date INT NOT NULL,
+	PRIMARY KEY (guild_id, discord_role_id, role)
+);
+
+INSERT INTO RoleGrant (guild_id, user_id, role, granted_date)
+SELECT guild_id, director_id, 'director', created_date FROM Guild
+WHERE director_id IS NOT NULL;
+
+ALTER TABLE Guild DROP COLUMN director_id;
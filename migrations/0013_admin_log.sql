@@ -0,0 +1,15 @@
+-- Privileged commands, including the attempts that were denied.
+CREATE TABLE AdminLog (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	guild_id INT NOT NULL,
+	user_id INT NOT NULL,
+	command VARCHAR(32) NOT NULL,
+	arguments VARCHAR(255) NOT NULL,
+	outcome VARCHAR(16) NOT NULL,
+	log_date INT NOT NULL
+);
+
+CREATE INDEX AdminLogGuild ON AdminLog (guild_id, id);
+
+-- Channel receiving a live copy of each AdminLog row.
+ALTER TABLE Guild ADD COLUMN log_channel_id INT;
@@ -0,0 +1,22 @@
+-- How the bank behaves in each guild. A missing row or NULL column means the
+-- default: the `!` prefix and no announcement or log channel.
+CREATE TABLE GuildSettings (
+	guild_id INT PRIMARY KEY,
+	prefix VARCHAR(8),
+	announcement_channel_id INT,
+	log_channel_id INT
+);
+
+INSERT INTO GuildSettings (guild_id, log_channel_id)
+SELECT guild_id, log_channel_id FROM Guild WHERE log_channel_id IS NOT NULL;
+
+ALTER TABLE Guild DROP COLUMN log_channel_id;
+
+-- Channels where bank commands are allowed. A guild without rows allows them
+-- in every channel.
+CREATE TABLE AllowedChannel (
+	guild_id INT NOT NULL,
+	channel_id INT NOT NULL,
+	added_date INT NOT NULL,
+	PRIMARY KEY (guild_id, channel_id)
+);
@@ -0,0 +1,3 @@
+-- Which content a row's hash covers. NULL rows were sealed before the guild
+-- was hashed and keep their hashes; rows sealed from now on record 2.
+ALTER TABLE Transfer ADD COLUMN hash_version INT;
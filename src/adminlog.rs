@@ -0,0 +1,148 @@
+use sqlx::SqlitePool;
+
+use crate::operation::{get_current_time, GuildId, UserId, MAX_DESCRIPTION_LENGTH};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+	Done,
+	Denied,
+	Failed,
+}
+
+impl Outcome {
+	pub fn name(self) -> &'static str {
+		match self {
+			Outcome::Done => "done",
+			Outcome::Denied => "denied",
+			Outcome::Failed => "failed",
+		}
+	}
+}
+
+impl TryFrom<&str> for Outcome {
+	type Error = anyhow::Error;
+
+	fn try_from(value: &str) -> Result<Self, Self::Error> {
+		match value {
+			"done" => Ok(Outcome::Done),
+			"denied" => Ok(Outcome::Denied),
+			"failed" => Ok(Outcome::Failed),
+			_ => anyhow::bail!("Unknown outcome {}.", value),
+		}
+	}
+}
+
+#[derive(sqlx::FromRow)]
+struct AdminLogRow {
+	id: i64,
+	guild_id: GuildId,
+	user_id: UserId,
+	command: String,
+	arguments: String,
+	outcome: String,
+	log_date: i64,
+}
+
+pub struct AdminLogEntry {
+	pub id: i64,
+	pub guild_id: GuildId,
+	pub user_id: UserId,
+	pub command: String,
+	pub arguments: String,
+	pub outcome: Outcome,
+	pub date: i64,
+}
+
+impl TryFrom<AdminLogRow> for AdminLogEntry {
+	type Error = anyhow::Error;
+
+	fn try_from(row: AdminLogRow) -> Result<Self, Self::Error> {
+		Ok(AdminLogEntry {
+			id: row.id,
+			guild_id: row.guild_id,
+			user_id: row.user_id,
+			command: row.command,
+			arguments: row.arguments,
+			outcome: Outcome::try_from(row.outcome.as_str())?,
+			date: row.log_date,
+		})
+	}
+}
+
+/// Records a privileged command. `content` is the whole message, split here
+/// into the command and its arguments.
+pub async fn log_command(
+	pool: &SqlitePool,
+	guild: GuildId,
+	user: UserId,
+	content: &str,
+	outcome: Outcome,
+) -> anyhow::Result<AdminLogEntry> {
+	let content = content.trim();
+	let (command, arguments) = content
+		.split_once(char::is_whitespace)
+		.unwrap_or((content, ""));
+	let arguments: String = arguments
+		.trim()
+		.chars()
+		.take(MAX_DESCRIPTION_LENGTH)
+		.collect();
+	let date = get_current_time();
+
+	let id = sqlx::query(
+		r#"INSERT INTO AdminLog (guild_id, user_id, command, arguments, outcome, log_date)
+		VALUES (?, ?, ?, ?, ?, ?)"#,
+	)
+	.bind(guild)
+	.bind(user)
+	.bind(command)
+	.bind(&arguments)
+	.bind(outcome.name())
+	.bind(date)
+	.execute(pool)
+	.await?
+	.last_insert_rowid();
+
+	Ok(AdminLogEntry {
+		id,
+		guild_id: guild,
+		user_id: user,
+		command: command.to_string(),
+		arguments,
+		outcome,
+		date,
+	})
+}
+
+pub async fn count_log(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<i64> {
+	Ok(
+		sqlx::query_as::<_, (i64,)>("SELECT COUNT(*) FROM AdminLog WHERE guild_id=?")
+			.bind(guild)
+			.fetch_one(pool)
+			.await?
+			.0,
+	)
+}
+
+/// Entries of `guild`, newest first.
+pub async fn get_log(
+	pool: &SqlitePool,
+	guild: GuildId,
+	offset: i64,
+	limit: i64,
+) -> anyhow::Result<Vec<AdminLogEntry>> {
+	sqlx::query_as::<_, AdminLogRow>(
+		r#"SELECT * FROM AdminLog
+		WHERE guild_id=?
+		ORDER BY id DESC
+		LIMIT ? OFFSET ?"#,
+	)
+	.bind(guild)
+	.bind(limit)
+	.bind(offset)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(AdminLogEntry::try_from)
+	.collect()
+}
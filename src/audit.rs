@@ -0,0 +1,248 @@
+use sqlx::SqlitePool;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::chain::{self, ChainBreak};
+use crate::currency::{Currency, Money};
+use crate::guild;
+use crate::operation::{GuildId, LedgerRow, UserId, BANK_ID};
+
+/// A place where the stored ledger disagrees with its replay.
+#[derive(Debug)]
+pub enum LedgerIssue {
+	/// A row whose balance snapshot does not follow from the account's
+	/// previous snapshot and the row's value.
+	Snapshot {
+		id: u32,
+		guild: GuildId,
+		currency: String,
+		account: UserId,
+		expected: i64,
+		stored: i64,
+	},
+	/// An `Account` balance that differs from the account's last snapshot.
+	Account {
+		guild: GuildId,
+		currency: String,
+		account: UserId,
+		expected: i64,
+		stored: i64,
+	},
+	/// The first place where the hash chain is broken.
+	Chain(ChainBreak),
+	/// Non-bank balances that do not add up to minus the bank's balance.
+	Supply {
+		guild: GuildId,
+		currency: String,
+		accounts_total: i64,
+		bank_balance: i64,
+	},
+}
+
+pub struct AuditReport {
+	pub guild: GuildId,
+	pub rows_checked: usize,
+	pub issues: Vec<LedgerIssue>,
+}
+
+fn format_units(guild: GuildId, code: &str, value: i64) -> String {
+	match Currency::try_from((guild, code)) {
+		Ok(currency) => Money::new(value, currency).to_string(),
+		Err(_) => format!("{} {} (minor units)", code, value),
+	}
+}
+
+impl fmt::Display for LedgerIssue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			LedgerIssue::Snapshot {
+				id,
+				guild,
+				currency,
+				account,
+				expected,
+				stored,
+			} => write!(
+				f,
+				"Transfer #{}: account {} should hold {} but the snapshot says {}.",
+				id,
+				account,
+				format_units(*guild, currency, *expected),
+				format_units(*guild, currency, *stored)
+			),
+			LedgerIssue::Account {
+				guild,
+				currency,
+				account,
+				expected,
+				stored,
+			} => write!(
+				f,
+				"Account {}: the balance table holds {} but the ledger ends at {}.",
+				account,
+				format_units(*guild, currency, *stored),
+				format_units(*guild, currency, *expected)
+			),
+			LedgerIssue::Chain(chain_break) => write!(f, "{}", chain_break),
+			LedgerIssue::Supply {
+				guild,
+				currency,
+				accounts_total,
+				bank_balance,
+			} => write!(
+				f,
+				"{}: accounts hold {} in total but the bank balance is {}.",
+				currency,
+				format_units(*guild, currency, *accounts_total),
+				format_units(*guild, currency, *bank_balance)
+			),
+		}
+	}
+}
+
+/// Replays the whole ledger in order and checks that every balance snapshot
+/// chains from the previous one of the same account. Each row is checked
+/// against the stored snapshot before it, so a single broken row is reported
+/// once instead of tainting the rest of the account's history.
+///
+/// The last snapshots are then compared with the `Account` table, every
+/// currency is checked to sum to zero against the bank, and the hash chain is
+/// walked up to its first broken link. Only the ledger of `guild` is checked.
+pub async fn verify_ledger(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<AuditReport> {
+	let rows =
+		sqlx::query_as::<_, LedgerRow>("SELECT * FROM Transfer WHERE guild_id=? ORDER BY id")
+			.bind(guild)
+			.fetch_all(pool)
+			.await?;
+
+	let mut issues = vec![];
+	let mut balances: BTreeMap<(String, UserId), i64> = BTreeMap::new();
+
+	for row in rows.iter() {
+		let from_key = (row.currency.clone(), row.from_account);
+		let to_key = (row.currency.clone(), row.to_account);
+
+		let before_from = balances.get(&from_key).copied().unwrap_or(0);
+		let before_to = balances.get(&to_key).copied().unwrap_or(0);
+
+		let checks = [
+			(row.from_account, before_from - row.value, row.from_balance),
+			(row.to_account, before_to + row.value, row.to_balance),
+		];
+
+		for (account, expected, stored) in checks {
+			if expected != stored {
+				issues.push(LedgerIssue::Snapshot {
+					id: row.id,
+					guild,
+					currency: row.currency.clone(),
+					account,
+					expected,
+					stored,
+				});
+			}
+		}
+
+		balances.insert(from_key, row.from_balance);
+		balances.insert(to_key, row.to_balance);
+	}
+
+	let stored_accounts = sqlx::query_as::<_, (UserId, String, i64)>(
+		"SELECT account, currency, balance FROM Account WHERE guild_id=?",
+	)
+	.bind(guild)
+	.fetch_all(pool)
+	.await?;
+
+	let stored_accounts: HashMap<(String, UserId), i64> = stored_accounts
+		.into_iter()
+		.map(|(account, currency, balance)| ((currency, account), balance))
+		.collect();
+
+	for ((currency, account), expected) in balances.iter() {
+		let stored = stored_accounts
+			.get(&(currency.clone(), *account))
+			.copied()
+			.unwrap_or(0);
+
+		if stored != *expected {
+			issues.push(LedgerIssue::Account {
+				guild,
+				currency: currency.clone(),
+				account: *account,
+				expected: *expected,
+				stored,
+			});
+		}
+	}
+
+	for ((currency, account), stored) in stored_accounts.iter() {
+		if !balances.contains_key(&(currency.clone(), *account)) && *stored != 0 {
+			issues.push(LedgerIssue::Account {
+				guild,
+				currency: currency.clone(),
+				account: *account,
+				expected: 0,
+				stored: *stored,
+			});
+		}
+	}
+
+	let mut totals: BTreeMap<&str, (i64, i64)> = BTreeMap::new();
+	for ((currency, account), balance) in balances.iter() {
+		let total = totals.entry(currency.as_str()).or_default();
+		if *account == BANK_ID {
+			total.1 += balance;
+		} else {
+			total.0 += balance;
+		}
+	}
+
+	for (currency, (accounts_total, bank_balance)) in totals {
+		if accounts_total != -bank_balance {
+			issues.push(LedgerIssue::Supply {
+				guild,
+				currency: currency.to_owned(),
+				accounts_total,
+				bank_balance,
+			});
+		}
+	}
+
+	if let Some(chain_break) = chain::verify_chain(pool, guild).await? {
+		issues.push(LedgerIssue::Chain(chain_break));
+	}
+
+	Ok(AuditReport {
+		guild,
+		rows_checked: rows.len(),
+		issues,
+	})
+}
+
+/// Offline mode, run as `bankbot audit`: prints the report of every guild and
+/// tells whether all ledgers are sound.
+pub async fn run_offline(pool: &SqlitePool) -> anyhow::Result<bool> {
+	let mut sound = true;
+
+	for guild in guild::get_guilds(pool).await? {
+		let report = verify_ledger(pool, guild).await?;
+
+		println!(
+			"Guild {}: checked {} ledger rows.",
+			report.guild, report.rows_checked
+		);
+		for issue in report.issues.iter() {
+			println!("{}", issue);
+		}
+
+		if report.issues.is_empty() {
+			println!("No issues found.");
+		} else {
+			println!("{} issues found.", report.issues.len());
+			sound = false;
+		}
+	}
+
+	Ok(sound)
+}
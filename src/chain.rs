@@ -0,0 +1,178 @@
+use sha2::{Digest, Sha256};
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::operation::{GuildId, LedgerRow};
+
+/// Content covered by the hashes of newly sealed rows. Version 2 adds the
+/// guild; rows without a version predate it.
+pub const HASH_VERSION: i64 = 2;
+
+/// The `previous_hash` of the first ledger row of every guild.
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+pub struct ChainHead {
+	pub id: u32,
+	/// `None` when the last row is unsealed, which the audit reports.
+	pub hash: Option<String>,
+	pub date: i64,
+}
+
+#[derive(Debug)]
+pub enum ChainBreak {
+	/// The row does not point at the hash of the row before it.
+	PreviousHash { id: u32 },
+	/// The row's content no longer matches its own hash.
+	Hash { id: u32 },
+	/// The row was never sealed.
+	Unsealed { id: u32 },
+}
+
+impl std::fmt::Display for ChainBreak {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ChainBreak::PreviousHash { id } => write!(
+				f,
+				"Transfer #{} does not link to the hash of the row before it.",
+				id
+			),
+			ChainBreak::Hash { id } => {
+				write!(f, "Transfer #{} was modified after it was written.", id)
+			}
+			ChainBreak::Unsealed { id } => write!(f, "Transfer #{} has no hash.", id),
+		}
+	}
+}
+
+/// Hashes the content of a ledger row together with the hash of the row
+/// before it. Columns added after the chain was introduced are only appended
+/// when set, and the guild from `hash_version` 2 on, so older rows keep their
+/// hashes.
+pub fn hash_row(row: &LedgerRow, previous_hash: &str) -> String {
+	let mut content = format!(
+		"{}|{}|{}|{}|{}|{}|{}|{}|{:?}|{}",
+		row.id,
+		row.currency,
+		row.from_account,
+		row.to_account,
+		row.from_balance,
+		row.to_balance,
+		row.value,
+		row.transfer_date,
+		row.description,
+		previous_hash
+	);
+
+	if let Some(reverses) = row.reverses {
+		content.push_str(&format!("|reverses:{}", reverses));
+	}
+
+	if row.hash_version.unwrap_or(1) >= 2 {
+		content.push_str(&format!("|guild:{}", row.guild_id));
+	}
+
+	format!("{:x}", Sha256::digest(content.as_bytes()))
+}
+
+/// Links the row `id` to the row before it in the same guild, as every guild
+/// keeps its own chain. Must run in the transaction that wrote the row, so no
+/// other row can be sealed in between.
+pub async fn seal_row(conn: &mut SqliteConnection, id: i64) -> anyhow::Result<()> {
+	let previous = sqlx::query_as::<_, (u32, Option<String>)>(
+		r#"SELECT id, hash FROM Transfer
+		WHERE id < ? AND guild_id=(SELECT guild_id FROM Transfer WHERE id=?)
+		ORDER BY id DESC
+		LIMIT 1"#,
+	)
+	.bind(id)
+	.bind(id)
+	.fetch_optional(&mut *conn)
+	.await?;
+
+	// Linking to an unsealed row would build a chain that cannot verify.
+	let previous_hash = match previous {
+		Some((_, Some(hash))) => hash,
+		Some((previous_id, None)) => {
+			anyhow::bail!(
+				"Cannot seal transfer #{}: the transfer before it, #{}, is unsealed.",
+				id,
+				previous_id
+			)
+		}
+		None => String::from(GENESIS_HASH),
+	};
+
+	let mut row = sqlx::query_as::<_, LedgerRow>("SELECT * FROM Transfer WHERE id=?")
+		.bind(id)
+		.fetch_one(&mut *conn)
+		.await?;
+	row.hash_version = Some(HASH_VERSION);
+
+	sqlx::query("UPDATE Transfer SET previous_hash=?, hash=?, hash_version=? WHERE id=?")
+		.bind(&previous_hash)
+		.bind(hash_row(&row, &previous_hash))
+		.bind(HASH_VERSION)
+		.bind(id)
+		.execute(&mut *conn)
+		.await?;
+
+	Ok(())
+}
+
+/// Seals the rows written before the ledger was hash chained. Runs once, in
+/// the migration transaction that brings an older database up to date; rows
+/// found unhashed afterwards are left for the audit to report.
+pub async fn seal_unhashed_rows(conn: &mut SqliteConnection) -> anyhow::Result<()> {
+	let ids = sqlx::query_as::<_, (i64,)>("SELECT id FROM Transfer WHERE hash IS NULL ORDER BY id")
+		.fetch_all(&mut *conn)
+		.await?;
+
+	for (id,) in ids {
+		seal_row(conn, id).await?;
+	}
+
+	Ok(())
+}
+
+pub async fn get_head(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<Option<ChainHead>> {
+	let head = sqlx::query_as::<_, (u32, Option<String>, i64)>(
+		r#"SELECT id, hash, transfer_date FROM Transfer
+		WHERE guild_id=?
+		ORDER BY id DESC
+		LIMIT 1"#,
+	)
+	.bind(guild)
+	.fetch_optional(pool)
+	.await?;
+
+	Ok(head.map(|(id, hash, date)| ChainHead { id, hash, date }))
+}
+
+/// Walks the ledger of `guild` from the first row and returns the first broken
+/// link.
+pub async fn verify_chain(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<Option<ChainBreak>> {
+	let rows =
+		sqlx::query_as::<_, LedgerRow>("SELECT * FROM Transfer WHERE guild_id=? ORDER BY id")
+			.bind(guild)
+			.fetch_all(pool)
+			.await?;
+
+	let mut previous_hash = String::from(GENESIS_HASH);
+	for row in rows {
+		let (stored_previous, stored_hash) = match (&row.previous_hash, &row.hash) {
+			(Some(stored_previous), Some(stored_hash)) => (stored_previous, stored_hash),
+			_ => return Ok(Some(ChainBreak::Unsealed { id: row.id })),
+		};
+
+		if *stored_previous != previous_hash {
+			return Ok(Some(ChainBreak::PreviousHash { id: row.id }));
+		}
+
+		if *stored_hash != hash_row(&row, &previous_hash) {
+			return Ok(Some(ChainBreak::Hash { id: row.id }));
+		}
+
+		previous_hash = stored_hash.clone();
+	}
+
+	Ok(None)
+}
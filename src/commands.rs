@@ -1,9 +1,36 @@
+use chrono::{Days, NaiveDate, TimeZone, Utc};
+use chrono_tz::Tz;
+use serenity::builder::{CreateComponents, CreateEmbed};
 use serenity::model::channel::Message;
+use serenity::model::id::ChannelId;
+use serenity::model::interactions::application_command::ApplicationCommandInteraction;
+use serenity::model::interactions::message_component::{ButtonStyle, MessageComponentInteraction};
+use serenity::model::interactions::InteractionResponseType;
+use serenity::model::user::User;
 use serenity::prelude::*;
+use sqlx::SqlitePool;
 use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
-use crate::currency::{Currency, CurrencyInfo, ALL_CURRENCIES};
-use crate::operation::{force_transfer, get_balance, get_statement, send_transfer, TransferStatus};
+use crate::adminlog::{self, AdminLogEntry, Outcome};
+use crate::audit;
+use crate::chain;
+use crate::currency::{self, Currency, CurrencyInfo, Money, MoneyStyle};
+use crate::database;
+use crate::exchange::{self, ExchangeStatus};
+use crate::guild::{self, PrefixCache};
+use crate::invoice::{self, InvoiceStatus, DEFAULT_INVOICE_EXPIRY_DAYS};
+use crate::operation::{
+	count_statement, force_transfer, get_balance, get_current_time, get_statement, get_transfer,
+	rebuild_balances, reverse_transfer, send_transfer, GuildId, OperationKind, ReversalStatus,
+	StatementFilter, TransferStatus, BANK_ID, MAX_DESCRIPTION_LENGTH,
+};
+use crate::pending::{self, CancelStatus, PendingTransfer};
+use crate::permission::{self, Permission, Role};
+use crate::router;
+use crate::settings;
+use crate::slash;
 use crate::stat;
 
 async fn send_simple_message(response: &str, ctx: &Context, msg: &Message) {
@@ -13,61 +40,376 @@ async fn send_simple_message(response: &str, ctx: &Context, msg: &Message) {
 		.ok();
 }
 
-pub async fn get_stat_command(ctx: &Context, msg: &Message) {
+/// Where a command came from: a prefix command message or a slash command.
+/// Commands shared by both reply through it.
+pub enum Invocation<'a> {
+	Message(&'a Message),
+	Slash {
+		interaction: &'a ApplicationCommandInteraction,
+		/// Whether the deferred response was replaced by a reply yet.
+		answered: AtomicBool,
+	},
+}
+
+impl<'a> Invocation<'a> {
+	/// Wraps a slash command whose response is deferred before any reply.
+	pub fn slash(interaction: &'a ApplicationCommandInteraction) -> Self {
+		Invocation::Slash {
+			interaction,
+			answered: AtomicBool::new(false),
+		}
+	}
+
+	pub fn author(&self) -> &User {
+		match self {
+			Invocation::Message(msg) => &msg.author,
+			Invocation::Slash { interaction, .. } => &interaction.user,
+		}
+	}
+
+	pub fn channel_id(&self) -> ChannelId {
+		match self {
+			Invocation::Message(msg) => msg.channel_id,
+			Invocation::Slash { interaction, .. } => interaction.channel_id,
+		}
+	}
+
+	pub fn guild_id(&self) -> Option<serenity::model::id::GuildId> {
+		match self {
+			Invocation::Message(msg) => msg.guild_id,
+			Invocation::Slash { interaction, .. } => interaction.guild_id,
+		}
+	}
+
+	/// Sends a reply. The first reply to a slash command replaces its deferred
+	/// response and the later ones are follow-ups.
+	async fn send(
+		&self,
+		ctx: &Context,
+		embed: CreateEmbed,
+		components: Option<CreateComponents>,
+	) -> serenity::Result<Message> {
+		match self {
+			Invocation::Message(msg) => {
+				msg.channel_id
+					.send_message(&ctx.http, |m| {
+						m.set_embed(embed);
+						if let Some(components) = components {
+							m.set_components(components);
+						}
+						m
+					})
+					.await
+			}
+			Invocation::Slash {
+				interaction,
+				answered,
+			} => {
+				if !answered.swap(true, Ordering::SeqCst) {
+					interaction
+						.edit_original_interaction_response(&ctx.http, |r| {
+							r.set_embed(embed);
+							if let Some(components) = components {
+								r.components(|c| {
+									*c = components;
+									c
+								});
+							}
+							r
+						})
+						.await
+				} else {
+					interaction
+						.create_followup_message(&ctx.http, |f| {
+							f.add_embed(embed);
+							if let Some(components) = components {
+								f.set_components(components);
+							}
+							f
+						})
+						.await
+				}
+			}
+		}
+	}
+
+	pub async fn say(&self, ctx: &Context, response: &str) {
+		let mut embed = CreateEmbed::default();
+		embed.description(response);
+		self.send(ctx, embed, None).await.ok();
+	}
+}
+
+/// The command prefix of `guild_id`, cached after the first read. Direct
+/// messages always use the default.
+pub async fn command_prefix(
+	ctx: &Context,
+	guild_id: Option<serenity::model::id::GuildId>,
+) -> String {
+	let guild = match guild_id {
+		Some(guild_id) => *guild_id.as_u64() as GuildId,
+		None => return String::from(guild::DEFAULT_PREFIX),
+	};
+
+	let cached = ctx
+		.data
+		.read()
+		.await
+		.get::<PrefixCache>()
+		.and_then(|prefixes| prefixes.get(&guild).cloned());
+	if let Some(prefix) = cached {
+		return prefix;
+	}
+
+	let pool = database::get_pool(ctx).await;
+	match guild::get_prefix(&pool, guild).await {
+		Ok(prefix) => {
+			if let Some(prefixes) = ctx.data.write().await.get_mut::<PrefixCache>() {
+				prefixes.insert(guild, prefix.clone());
+			}
+			prefix
+		}
+		Err(_) => String::from(guild::DEFAULT_PREFIX),
+	}
+}
+
+/// Drops the cached prefix of `guild`, once it changed.
+async fn forget_prefix(ctx: &Context, guild: GuildId) {
+	if let Some(prefixes) = ctx.data.write().await.get_mut::<PrefixCache>() {
+		prefixes.remove(&guild);
+	}
+}
+
+/// Replies with the usage of the command in `msg`, from the registry.
+async fn send_usage(ctx: &Context, msg: &Message) {
+	let prefix = command_prefix(ctx, msg.guild_id).await;
+	if let Some(command) = router::parse(&msg.content, &prefix) {
+		send_simple_message(
+			&format!("Usage: {}.", command.format_usage(&prefix)),
+			ctx,
+			msg,
+		)
+		.await;
+	}
+}
+
+/// Replies with what is wrong with the arguments of `msg`, followed by the
+/// usage of its command.
+async fn send_usage_error(reason: &str, ctx: &Context, msg: &Message) {
+	let prefix = command_prefix(ctx, msg.guild_id).await;
+	match router::parse(&msg.content, &prefix) {
+		Some(command) => {
+			send_simple_message(
+				&format!("{}\nUsage: {}.", reason, command.format_usage(&prefix)),
+				ctx,
+				msg,
+			)
+			.await
+		}
+		None => send_simple_message(reason, ctx, msg).await,
+	}
+}
+
+pub async fn help_command(ctx: &Context, msg: &Message) -> anyhow::Result<()> {
+	let prefix = command_prefix(ctx, msg.guild_id).await;
+	let name = msg.content.split_whitespace().nth(1);
+
+	let (title, response) =
+		match name.map(|name| name.strip_prefix(prefix.as_str()).unwrap_or(name)) {
+			Some(name) => match router::find(name) {
+				Some(command) => {
+					let mut response = format!(
+						"{}\n\n**Usage:** {}",
+						command.help,
+						command.format_usage(&prefix)
+					);
+					if !command.aliases.is_empty() {
+						let aliases = command
+							.aliases
+							.iter()
+							.map(|alias| format!("`{}{}`", prefix, alias))
+							.collect::<Vec<_>>()
+							.join(", ");
+						response.push_str(&format!("\n**Aliases:** {}", aliases));
+					}
+					if let Some(permission) = command.permission {
+						response.push_str(&format!(
+							"\n**Requires:** permission to {}",
+							permission.name()
+						));
+					}
+
+					(format!("{}{}", prefix, command.name), response)
+				}
+				None => {
+					send_simple_message(
+						&format!(
+							"There is no `{}{}` command. See `{}help`.",
+							prefix, name, prefix
+						),
+						ctx,
+						msg,
+					)
+					.await;
+					return Ok(());
+				}
+			},
+			None => {
+				let list = |restricted: bool| {
+					router::COMMANDS
+						.iter()
+						.filter(|command| command.permission.is_some() == restricted)
+						.map(|command| format!("`{}{}` {}", prefix, command.name, command.help))
+						.collect::<Vec<_>>()
+						.join("\n")
+				};
+
+				(
+					String::from("Commands"),
+					format!(
+						"{}\n\n**Restricted**\n{}\n\nUse `{}help <command>` for its usage.",
+						list(false),
+						list(true),
+						prefix
+					),
+				)
+			}
+		};
+
+	msg.channel_id
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| e.title(title).description(response))
+		})
+		.await?;
+
+	Ok(())
+}
+
+/// Collects the free text after the last parsed argument as the operation memo,
+/// leaving out user mentions.
+fn parse_memo(content: &str, skip: usize) -> Option<String> {
+	let memo = content
+		.split_whitespace()
+		.skip(skip)
+		.filter(|word| !(word.starts_with("<@") && word.ends_with('>')))
+		.collect::<Vec<_>>()
+		.join(" ")
+		.replace('`', "'");
+
+	if memo.is_empty() {
+		None
+	} else {
+		Some(memo.chars().take(MAX_DESCRIPTION_LENGTH).collect())
+	}
+}
+
+/// Finds the currency and the amount of a payment command, returning the
+/// amount in minor units and the index of the first memo word.
+fn parse_payment(guild: GuildId, content: &str) -> Result<(Currency, i64, usize), String> {
+	let mut currency: Option<Currency> = None;
+	let mut value: Option<&str> = None;
+	let mut memo_start = 0;
+
+	for (index, word) in content.split_whitespace().enumerate() {
+		if currency.is_none() {
+			currency = Currency::try_from((guild, word)).ok();
+		}
+		if value.is_none() && currency::is_amount(word) {
+			value = Some(word);
+		}
+
+		if currency.is_some() && value.is_some() {
+			memo_start = index + 1;
+			break;
+		}
+	}
+
+	let currency = currency.ok_or("Currency has not been detected. Specify a currency.")?;
+	if CurrencyInfo::from(currency).retired {
+		return Err(String::from("This currency has been retired."));
+	}
+
+	let value = value.ok_or("A value has not been detected. Specify which value to transfer.")?;
+	let integer_value = currency::parse_amount(value, currency).map_err(|err| err.to_string())?;
+	if integer_value <= 0 {
+		return Err(String::from("Please insert a positive value."));
+	}
+
+	Ok((currency, integer_value, memo_start))
+}
+
+pub async fn get_stat_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
 	let currency = msg
 		.content
 		.split_whitespace()
-		.find_map(|word| Currency::try_from(word).ok());
+		.find_map(|word| Currency::try_from((guild, word)).ok());
 
-	let currency = match currency {
-		Some(currency) => currency,
+	match currency {
+		Some(currency) => show_stat(ctx, &Invocation::Message(msg), currency).await,
 		None => {
-			send_simple_message("Please specify a currency.", ctx, msg).await;
-			return;
+			send_usage_error("Please specify a currency.", ctx, msg).await;
+			Ok(())
 		}
-	};
+	}
+}
 
-	let supply = stat::get_money_supply(currency).await.unwrap() as f64;
-	let transfers = stat::get_all_transfers(currency).await.unwrap() as f64;
-	let tendency = stat::calc_balances(&stat::get_all_balances(currency).await.unwrap());
-	let info = CurrencyInfo::from(currency);
-	let factor = f64::powf(10.0, info.subunitexp as f64);
+pub async fn show_stat(
+	ctx: &Context,
+	inv: &Invocation<'_>,
+	currency: Currency,
+) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	let supply = stat::get_money_supply(&pool, currency).await?;
+	let transfers = stat::get_all_transfers(&pool, currency).await?;
+	let tendency = stat::calc_balances(&stat::get_all_balances(&pool, currency).await?);
 
 	let response = format!(
-		"Money Supply: `{} {:.02}`
-		GDP: `{} {:.02}`
+		"Money Supply: `{}`
+		GDP: `{}`
 		GINI: `{:.05}`
-		Median: `{} {:.02}`
-		Average: `{} {:.02}`",
-		info.code,
-		supply * factor,
-		info.code,
-		transfers * factor,
+		Median: `{}`
+		Average: `{}`",
+		Money::new(supply, currency),
+		Money::new(transfers, currency),
 		tendency.gini,
-		info.code,
-		tendency.median * factor,
-		info.code,
-		tendency.average * factor
+		Money::new(tendency.median.round() as i64, currency),
+		Money::new(tendency.average.round() as i64, currency),
 	);
 
-	send_simple_message(&response, ctx, msg).await;
+	inv.say(ctx, &response).await;
+	Ok(())
 }
 
-pub async fn get_balance_command(ctx: &Context, msg: &Message) -> anyhow::Result<()> {
-	let author_id: i64 = *msg.author.id.as_u64() as i64;
+pub async fn get_balance_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+) -> anyhow::Result<()> {
+	let currencies = msg.content.find(' ').map(|argument_index| {
+		msg.content[argument_index..]
+			.split_whitespace()
+			.filter_map(|word| Currency::try_from((guild, word)).ok())
+			.collect()
+	});
+
+	show_balance(ctx, &Invocation::Message(msg), guild, currencies).await
+}
+
+/// Shows the balance in `currencies` or, when not given, in every currency
+/// with a balance.
+pub async fn show_balance(
+	ctx: &Context,
+	inv: &Invocation<'_>,
+	guild: GuildId,
+	currencies: Option<Vec<Currency>>,
+) -> anyhow::Result<()> {
+	let author_id: i64 = *inv.author().id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
 	let image = "https://cdn.discordapp.com/attachments/1153482364907962509/1153482411871584267/currency_dollar_blue.png";
 
-	let (currencies, inject_all) = match msg.content.find(' ') {
-		Some(argument_index) => (
-			msg.content[argument_index..]
-				.split_whitespace()
-				.map(Currency::try_from)
-				.filter(Result::is_ok)
-				.map(Result::unwrap)
-				.collect(),
-			false,
-		),
-		None => (ALL_CURRENCIES.to_vec(), true),
+	let (currencies, inject_all) = match currencies {
+		Some(currencies) => (currencies, false),
+		None => (currency::all_currencies(guild), true),
 	};
 
 	let response: Cow<'_, str> = if currencies.is_empty() {
@@ -76,287 +418,2461 @@ pub async fn get_balance_command(ctx: &Context, msg: &Message) -> anyhow::Result
 		let mut result = String::new();
 
 		for currency in currencies {
-			let balance = get_balance(author_id, currency).await?;
+			let balance = get_balance(&pool, author_id, currency).await?;
 			if inject_all && balance == 0 {
 				continue;
 			}
 
-			let info = CurrencyInfo::from(currency);
-
-			let real_balance = balance as f32 * f32::powi(10.0, info.subunitexp);
-			result.push_str(&format!("`{} {:.02}`\n", info.code, real_balance));
+			result.push_str(&format!("`{}`\n", Money::new(balance, currency)));
 		}
 
 		if result.is_empty() {
-			"`KSN 0.00`".into()
+			match currency::active_currencies(guild).first() {
+				Some(currency) => format!("`{}`", Money::new(0, *currency)).into(),
+				None => "No currencies matched".into(),
+			}
 		} else {
 			result.into()
 		}
 	};
 
-	msg.channel_id
-		.send_message(&ctx.http, |m| {
-			m.embed(|e| e.title("Balance").description(response).thumbnail(image))
-		})
-		.await
-		.ok();
+	let mut embed = CreateEmbed::default();
+	embed
+		.title("Balance")
+		.description(response)
+		.thumbnail(image);
+	inv.send(ctx, embed, None).await.ok();
 
 	Ok(())
 }
 
-pub async fn get_statement_command(ctx: &Context, msg: &Message) -> anyhow::Result<()> {
-	let currency = msg
-		.content
-		.split_whitespace()
-		.find_map(|word| Currency::try_from(word).ok());
+const STATEMENT_PAGE_SIZE: i64 = 10;
+const PAGE_TIMEOUT: Duration = Duration::from_secs(120);
 
-	let currency = match currency {
-		Some(currency) => currency,
-		None => {
-			send_simple_message("Please specify a currency.", ctx, msg).await;
-			return Ok(());
-		}
-	};
+/// One page of a paged embed, such as the statement.
+struct EmbedPage {
+	embed: CreateEmbed,
+	page: i64,
+	pages: i64,
+}
 
+async fn build_statement_page(
+	pool: &SqlitePool,
+	account: i64,
+	currency: Currency,
+	filter: &StatementFilter,
+	timezone: Tz,
+	page: i64,
+) -> anyhow::Result<EmbedPage> {
 	let info = CurrencyInfo::from(currency);
-	let account = *msg.author.id.as_u64() as i64;
-	let statement = get_statement(account, currency).await?;
-	let mut overall_balance: f32 = 0.00;
+	let total = count_statement(pool, account, currency, filter).await?;
+	let pages = ((total + STATEMENT_PAGE_SIZE - 1) / STATEMENT_PAGE_SIZE).max(1);
+	let page = page.clamp(1, pages);
+
+	let balance = get_balance(pool, account, currency).await?;
+	let overall_balance = Money::new(balance, currency);
+	let statement = get_statement(
+		pool,
+		account,
+		currency,
+		filter,
+		(page - 1) * STATEMENT_PAGE_SIZE,
+		STATEMENT_PAGE_SIZE,
+	)
+	.await?;
 
-	let mut counter = 0;
 	let mut response: String = String::from("");
-	for transfer in statement {
-		let balance: f32 = (transfer.balance as f32) * f32::powf(10.0, info.subunitexp as f32);
-		let value: f32 = (transfer.value as f32) * f32::powf(10.0, info.subunitexp as f32);
-		if counter == 0 {
-			overall_balance = balance;
-		}
+	for transfer in statement.iter() {
+		let balance = Money::new(transfer.balance, transfer.currency);
+		let value = Money::new(transfer.value, transfer.currency);
 
-		response.push_str(&format!("Date: `{}`\n", transfer.date));
 		response.push_str(&format!(
-			"Balance: `{} {:.2}`. Operation: `{} {:.2}`\n",
-			info.code, balance, info.code, value
+			"`#{}` Date: {}\n",
+			transfer.id,
+			format_date(transfer.date, timezone)
 		));
-		if transfer.from_account == account {
-			response.push_str(&format!("Transfer sent to <@!{}>\n", transfer.to_account));
-		} else if transfer.from_account != 0 {
-			response.push_str(&format!(
+		response.push_str(&format!("Balance: `{}`. Operation: `{}`\n", balance, value));
+		match transfer.kind(account) {
+			OperationKind::Sent => {
+				response.push_str(&format!("Transfer sent to <@!{}>\n", transfer.to_account))
+			}
+			OperationKind::Received => response.push_str(&format!(
 				"Transfer received from <@!{}>\n",
 				transfer.from_account
-			));
-		} else {
-			response.push_str("Deposit received.\n");
+			)),
+			OperationKind::Deposit => response.push_str("Deposit received.\n"),
+			OperationKind::Charge => response.push_str("Charge applied.\n"),
+			OperationKind::Exchange => response.push_str("Currency exchange.\n"),
+		}
+		if let Some(reverses) = transfer.reverses {
+			response.push_str(&format!("Reversal of operation `#{}`.\n", reverses));
+		}
+		if let Some(exchange_id) = transfer.exchange_id {
+			if let Some(record) = exchange::get_exchange(pool, exchange_id).await? {
+				response.push_str(&format!("Exchanged at {}.\n", record.describe()));
+			}
+		}
+		if let Some(description) = &transfer.description {
+			response.push_str(&format!("Memo: `{}`\n", description));
+		}
+		if let Some(reversed_by) = transfer.reversed_by {
+			response.push_str(&format!("**Reversed** by operation `#{}`.\n", reversed_by));
 		}
 
 		response.push('\n');
-		counter += 1;
-		if counter > 10 {
-			break;
-		}
 	}
 
-	if counter == 0 {
+	if statement.is_empty() {
 		response = String::from("There are no transactions to report.");
 	}
 
-	msg.channel_id
-		.send_message(&ctx.http, |m| {
-			m.embed(|e| {
-				e.title(format!(
-					"{}: {} {:.2}",
-					info.name, info.code, overall_balance
-				))
-				.description(response)
-				.thumbnail(info.picture)
-			})
-		})
-		.await
-		.ok();
+	let mut embed = CreateEmbed::default();
+	embed
+		.title(format!(
+			"{}: {}",
+			info.name,
+			overall_balance.format(MoneyStyle::Symbol)
+		))
+		.description(response)
+		.thumbnail(info.picture)
+		.footer(|f| f.text(format!("Page {} of {}", page, pages)));
 
-	Ok(())
+	Ok(EmbedPage { embed, page, pages })
 }
 
-pub async fn transfer_command(ctx: &Context, msg: &Message) {
-	let mentions_vector = &msg.mentions;
+/// Previous and next buttons with the custom ids `<prefix>_previous` and
+/// `<prefix>_next`.
+fn page_buttons(prefix: &str, page: i64, pages: i64, enabled: bool) -> CreateComponents {
+	let mut components = CreateComponents::default();
+	components.create_action_row(|r| {
+		r.create_button(|b| {
+			b.custom_id(format!("{}_previous", prefix))
+				.label("Previous")
+				.style(ButtonStyle::Secondary)
+				.disabled(!enabled || page <= 1)
+		})
+		.create_button(|b| {
+			b.custom_id(format!("{}_next", prefix))
+				.label("Next")
+				.style(ButtonStyle::Secondary)
+				.disabled(!enabled || page >= pages)
+		})
+	});
 
-	if mentions_vector.is_empty() {
-		send_simple_message("Please ping the individual you want to transfer.", ctx, msg).await;
-		return;
-	}
+	components
+}
 
-	if mentions_vector.len() > 1 {
-		send_simple_message(
-			"You cannot transfer multiple times. Only a single transfer is allowed.",
-			ctx,
-			msg,
-		)
-		.await;
-		return;
+/// Formats epoch milliseconds as a local time in `timezone`, followed by a
+/// Discord relative timestamp.
+fn format_date(date: i64, timezone: Tz) -> String {
+	match Utc.timestamp_millis_opt(date).single() {
+		Some(utc) => format!(
+			"`{}` <t:{}:R>",
+			utc.with_timezone(&timezone).format("%Y-%m-%d %H:%M %Z"),
+			utc.timestamp()
+		),
+		None => format!("`{}`", date),
 	}
+}
 
-	if mentions_vector[0].bot {
-		send_simple_message("You cannot transfer to bots.", ctx, msg).await;
-		return;
-	}
+/// Parses a `YYYY-MM-DD` date into the epoch milliseconds of the midnight
+/// `days` later in `timezone`.
+pub fn parse_local_midnight(word: &str, timezone: Tz, days: u64) -> Option<i64> {
+	let date = NaiveDate::parse_from_str(word, "%Y-%m-%d")
+		.ok()?
+		.checked_add_days(Days::new(days))?;
 
-	let to_account = *mentions_vector[0].id.as_u64() as i64;
-	let from_account = *msg.author.id.as_u64() as i64;
-	if to_account == from_account {
-		send_simple_message("You cannot transfer to yourself.", ctx, msg).await;
-		return;
-	}
+	timezone
+		.from_local_datetime(&date.and_hms_opt(0, 0, 0)?)
+		.earliest()
+		.map(|date| date.timestamp_millis())
+}
 
-	let split_iterator = msg.content.split_whitespace();
-	let mut currency: Option<Currency> = None;
-	let mut value: Option<f64> = None;
+/// Reads the statement filters and page number from `!statement` arguments,
+/// such as `!statement USD transfers @alice since 2026-09-01 until 2026-10-01 2`.
+fn parse_statement_arguments(
+	msg: &Message,
+	timezone: Tz,
+) -> Result<(StatementFilter, i64), String> {
+	let mut filter = StatementFilter::default();
+	let mut page = 1;
 
-	for word in split_iterator {
-		if currency.is_none() {
-			currency = Currency::try_from(word).ok();
-		}
-		if value.is_none() {
-			value = word.parse::<f64>().ok();
-		}
+	let mut words = msg.content.split_whitespace().skip(1);
+	while let Some(word) = words.next() {
+		match word.to_lowercase().as_str() {
+			"deposit" | "deposits" => filter.kinds.push(OperationKind::Deposit),
+			"charge" | "charges" => filter.kinds.push(OperationKind::Charge),
+			"sent" => filter.kinds.push(OperationKind::Sent),
+			"received" => filter.kinds.push(OperationKind::Received),
+			"exchange" | "exchanges" => filter.kinds.push(OperationKind::Exchange),
+			"transfer" | "transfers" => {
+				filter.kinds.push(OperationKind::Sent);
+				filter.kinds.push(OperationKind::Received);
+			}
+			"since" | "until" => {
+				// The until date is inclusive, so stop at the following midnight.
+				let since = word.eq_ignore_ascii_case("since");
+				let days = if since { 0 } else { 1 };
 
-		if currency.is_some() && value.is_some() {
-			break;
+				let date = words
+					.next()
+					.and_then(|date| parse_local_midnight(date, timezone, days))
+					.ok_or_else(|| {
+						format!("Please give a date as `YYYY-MM-DD` after `{}`.", word)
+					})?;
+
+				if since {
+					filter.since = Some(date);
+				} else {
+					filter.until = Some(date);
+				}
+			}
+			_ => {
+				if let Ok(number) = word.parse::<i64>() {
+					page = number;
+				}
+			}
 		}
 	}
 
+	filter.counterparty = msg.mentions.first().map(|user| *user.id.as_u64() as i64);
+
+	Ok((filter, page))
+}
+
+pub async fn get_statement_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+) -> anyhow::Result<()> {
+	let currency = msg
+		.content
+		.split_whitespace()
+		.find_map(|word| Currency::try_from((guild, word)).ok());
+
 	let currency = match currency {
 		Some(currency) => currency,
 		None => {
+			send_usage_error("Please specify a currency.", ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	let account = *msg.author.id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+	let timezone = settings::get_timezone(&pool, account).await?;
+
+	let (filter, page) = match parse_statement_arguments(msg, timezone) {
+		Ok(arguments) => arguments,
+		Err(response) => {
+			send_usage_error(&response, ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	show_statement(
+		ctx,
+		&Invocation::Message(msg),
+		currency,
+		filter,
+		timezone,
+		page,
+	)
+	.await
+}
+
+/// Sends the statement page by page, with buttons that only the requester can
+/// use to turn the pages.
+pub async fn show_statement(
+	ctx: &Context,
+	inv: &Invocation<'_>,
+	currency: Currency,
+	filter: StatementFilter,
+	timezone: Tz,
+	page: i64,
+) -> anyhow::Result<()> {
+	let account = *inv.author().id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+
+	let mut current =
+		build_statement_page(&pool, account, currency, &filter, timezone, page).await?;
+
+	let components =
+		(current.pages > 1).then(|| page_buttons("statement", current.page, current.pages, true));
+	let mut message = inv.send(ctx, current.embed.clone(), components).await?;
+
+	if current.pages <= 1 {
+		return Ok(());
+	}
+
+	// Only the requesting user may turn the pages, until the buttons expire.
+	while let Some(interaction) = message
+		.await_component_interaction(ctx)
+		.author_id(inv.author().id)
+		.timeout(PAGE_TIMEOUT)
+		.await
+	{
+		let page = match interaction.data.custom_id.as_str() {
+			"statement_previous" => current.page - 1,
+			"statement_next" => current.page + 1,
+			_ => current.page,
+		};
+
+		current = build_statement_page(&pool, account, currency, &filter, timezone, page).await?;
+		interaction
+			.create_interaction_response(&ctx.http, |r| {
+				r.kind(InteractionResponseType::UpdateMessage)
+					.interaction_response_data(|d| {
+						d.set_embed(current.embed.clone())
+							.set_components(page_buttons(
+								"statement",
+								current.page,
+								current.pages,
+								true,
+							))
+					})
+			})
+			.await
+			.ok();
+	}
+
+	message
+		.edit(&ctx.http, |m| {
+			m.set_components(page_buttons(
+				"statement",
+				current.page,
+				current.pages,
+				false,
+			))
+		})
+		.await
+		.ok();
+
+	Ok(())
+}
+
+pub async fn timezone_command(ctx: &Context, msg: &Message) -> anyhow::Result<()> {
+	let account = *msg.author.id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+
+	let name = match msg.content.split_whitespace().nth(1) {
+		Some(name) => name,
+		None => {
+			let timezone = settings::get_timezone(&pool, account).await?;
+			let prefix = command_prefix(ctx, msg.guild_id).await;
 			send_simple_message(
-				"Currency has not been detected. Specify a currency.",
+				&format!(
+					"Your timezone is `{}`. Change it with `{}timezone America/Sao_Paulo`.",
+					timezone.name(),
+					prefix
+				),
 				ctx,
 				msg,
 			)
 			.await;
-			return;
+			return Ok(());
 		}
 	};
 
-	let value = match value {
-		Some(value) => value,
-		None => {
+	let timezone = match name.parse::<Tz>() {
+		Ok(timezone) => timezone,
+		Err(_) => {
 			send_simple_message(
-				"A value has not been detected. Specify which value to transfer.",
+				"Unknown timezone. Use an IANA name such as `America/Sao_Paulo` or `Europe/London`.",
 				ctx,
 				msg,
 			)
 			.await;
-			return;
+			return Ok(());
 		}
 	};
 
-	// Treat value.
-	let exp = CurrencyInfo::from(currency).subunitexp as f64;
-	let inter_value = f64::floor(value * f64::powf(10.0, -exp));
-	let integer_value: i64 = inter_value as i64;
+	settings::set_timezone(&pool, account, timezone).await?;
+	send_simple_message(
+		&format!("Your timezone is now `{}`.", timezone.name()),
+		ctx,
+		msg,
+	)
+	.await;
 
-	// Check for positiveness.
-	if integer_value <= 0 {
-		send_simple_message("Please insert a positive value.", ctx, msg).await;
-		return;
-	}
+	Ok(())
+}
 
-	// Make the transfer!
-	let transfer_status = send_transfer(from_account, to_account, currency, integer_value).await;
+/// Guilds using the bank that the author of `msg` is a member of, with their
+/// names.
+async fn member_guilds(ctx: &Context, msg: &Message) -> anyhow::Result<Vec<(GuildId, String)>> {
+	let pool = database::get_pool(ctx).await;
 
-	let status_response = match transfer_status {
-		TransferStatus::Authorized => "Transfer authorized.",
-		TransferStatus::InsuficientBalance => "Insuficient balance for this transfer.",
-		TransferStatus::BadValue => "Inserted value is bad.",
-		TransferStatus::Failed => "The transfer was not authorized, and blocked.",
-	};
+	let mut guilds = Vec::new();
+	for guild_id in guild::get_guilds(&pool).await? {
+		// The Discord API answers with an error when the user is not a member.
+		if ctx
+			.http
+			.get_member(guild_id as u64, *msg.author.id.as_u64())
+			.await
+			.is_err()
+		{
+			continue;
+		}
 
-	send_simple_message(status_response, ctx, msg).await;
-}
+		let name = match ctx.http.get_guild(guild_id as u64).await {
+			Ok(partial) => partial.name,
+			Err(_) => guild_id.to_string(),
+		};
+		guilds.push((guild_id, name));
+	}
 
-pub async fn create_deposit_command(ctx: &Context, msg: &Message) {
-	let mentions_vector = &msg.mentions;
+	Ok(guilds)
+}
 
-	if mentions_vector.len() >= 2 {
+pub async fn guild_command(ctx: &Context, msg: &Message) -> anyhow::Result<()> {
+	if msg.guild_id.is_some() {
 		send_simple_message(
-			"You cannot transfer multiple times. Only a single transfer is allowed.",
+			"Commands sent here use this server's ledger. Use `!guild` in a direct message to pick the server your direct messages act on.",
 			ctx,
 			msg,
 		)
 		.await;
-		return;
+		return Ok(());
 	}
 
-	// Find target.
-	let target_id: i64 = if mentions_vector.is_empty() {
-		*msg.author.id.as_u64() as i64
-	} else {
-		if mentions_vector[0].bot {
-			send_simple_message("You cannot transfer to bots.", ctx, msg).await;
-			return;
-		}
-
-		*mentions_vector[0].id.as_u64() as i64
-	};
-
-	// Find currency and value.
-	let split_iterator = msg.content.split_whitespace();
-
-	let mut currency: Option<Currency> = None;
-	let mut value: Option<f64> = None;
+	let account = *msg.author.id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+	let guilds = member_guilds(ctx, msg).await?;
 
-	for word in split_iterator {
-		if currency.is_none() {
-			currency = Currency::try_from(word).ok();
-		}
-		if value.is_none() {
-			value = word.parse().ok();
-		}
+	let choice = match msg.content.split_whitespace().nth(1) {
+		Some(choice) => choice,
+		None => {
+			if guilds.is_empty() {
+				send_simple_message("None of your servers use the bank yet.", ctx, msg).await;
+				return Ok(());
+			}
 
-		if value.is_some() && currency.is_some() {
-			break;
-		}
-	}
+			let current = settings::get_dm_guild(&pool, account).await?;
+			let list = guilds
+				.iter()
+				.enumerate()
+				.map(|(i, (guild_id, name))| {
+					let marker = if current == Some(*guild_id) {
+						" (current)"
+					} else {
+						""
+					};
+					format!("`{}` {}{}", i + 1, name, marker)
+				})
+				.collect::<Vec<_>>()
+				.join("\n");
 
-	let currency = match currency {
-		Some(currency) => currency,
-		None => {
 			send_simple_message(
-				"Currency has not been detected. Specify a currency.",
+				&format!("{}\n\nPick one with `!guild <number>`.", list),
 				ctx,
 				msg,
 			)
 			.await;
-			return;
+			return Ok(());
 		}
 	};
 
-	let value = match value {
-		Some(value) => value,
+	// Accept either the position in the list or the guild ID itself.
+	let chosen = choice.parse::<i64>().ok().and_then(|number| {
+		guilds
+			.iter()
+			.find(|(guild_id, _)| *guild_id == number)
+			.or_else(|| guilds.get((number as usize).wrapping_sub(1)))
+	});
+
+	match chosen {
+		Some((guild_id, name)) => {
+			settings::set_dm_guild(&pool, account, *guild_id).await?;
+			send_simple_message(
+				&format!("Your direct messages now use the ledger of {}.", name),
+				ctx,
+				msg,
+			)
+			.await;
+		}
 		None => {
 			send_simple_message(
-				"A value has not been detected. Specify which value to transfer.",
+				"You are not in that server. Use `!guild` to list your servers.",
 				ctx,
 				msg,
 			)
 			.await;
+		}
+	}
+
+	Ok(())
+}
+
+pub fn denial_message(permission: Permission) -> String {
+	format!(
+		"You are not allowed to {} in this ledger.",
+		permission.name()
+	)
+}
+
+/// Tells the author of `msg` they lack `permission`.
+pub async fn permission_denied(ctx: &Context, msg: &Message, permission: Permission) {
+	send_simple_message(&denial_message(permission), ctx, msg).await;
+}
+
+/// Parses the Discord role mentions of a message, which also works in direct
+/// messages where Discord does not resolve them.
+fn parse_role_mentions(content: &str) -> Vec<i64> {
+	content
+		.split_whitespace()
+		.filter_map(|word| word.strip_prefix("<@&")?.strip_suffix('>')?.parse().ok())
+		.collect()
+}
+
+/// Parses a channel mention such as `<#123>`.
+fn parse_channel_mention(word: &str) -> Option<i64> {
+	word.strip_prefix("<#")?.strip_suffix('>')?.parse().ok()
+}
+
+/// Grants (or with `grant` false, revokes) a role to the mentioned users and
+/// Discord roles: `!grant teller @user @Cashiers`.
+pub async fn grant_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+	grant: bool,
+) -> anyhow::Result<()> {
+	let role = match msg
+		.content
+		.split_whitespace()
+		.find_map(|word| Role::try_from(word).ok())
+	{
+		Some(role) => role,
+		None => {
+			let names = permission::ROLES
+				.iter()
+				.map(|role| format!("`{}`", role.name()))
+				.collect::<Vec<_>>()
+				.join(", ");
+			send_usage_error(&format!("Roles: {}.", names), ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	let users: Vec<i64> = msg
+		.mentions
+		.iter()
+		.filter(|user| !user.bot)
+		.map(|user| *user.id.as_u64() as i64)
+		.collect();
+	let discord_roles = parse_role_mentions(&msg.content);
+	if users.is_empty() && discord_roles.is_empty() {
+		send_usage(ctx, msg).await;
+		return Ok(());
+	}
+
+	let pool = database::get_pool(ctx).await;
+	let mut response = String::new();
+
+	for user in users {
+		let changed = if grant {
+			permission::grant_role(&pool, guild, user, role).await?
+		} else {
+			permission::revoke_role(&pool, guild, user, role).await?
+		};
+		response.push_str(&match (grant, changed) {
+			(true, true) => format!("<@{}> is now {}.\n", user, role.name()),
+			(true, false) => format!("<@{}> was already {}.\n", user, role.name()),
+			(false, true) => format!("<@{}> is no longer {}.\n", user, role.name()),
+			(false, false) => format!("<@{}> was not {}.\n", user, role.name()),
+		});
+	}
+
+	for discord_role in discord_roles {
+		let changed = if grant {
+			permission::map_role(&pool, guild, discord_role, role).await?
+		} else {
+			permission::unmap_role(&pool, guild, discord_role, role).await?
+		};
+		response.push_str(&match (grant, changed) {
+			(true, true) => format!("Members of <@&{}> are now {}.\n", discord_role, role.name()),
+			(true, false) => format!(
+				"Members of <@&{}> were already {}.\n",
+				discord_role,
+				role.name()
+			),
+			(false, true) => format!(
+				"Members of <@&{}> are no longer {}.\n",
+				discord_role,
+				role.name()
+			),
+			(false, false) => format!("<@&{}> was not mapped to {}.\n", discord_role, role.name()),
+		});
+	}
+
+	send_simple_message(&response, ctx, msg).await;
+
+	Ok(())
+}
+
+pub async fn roles_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	let grants = permission::get_role_grants(&pool, guild).await?;
+	let mappings = permission::get_role_mappings(&pool, guild).await?;
+
+	let mut response = String::new();
+	for role in permission::ROLES {
+		let holders = grants
+			.iter()
+			.filter(|(_, granted)| *granted == role)
+			.map(|(user, _)| format!("<@{}>", user))
+			.chain(
+				mappings
+					.iter()
+					.filter(|(_, mapped)| *mapped == role)
+					.map(|(discord_role, _)| format!("<@&{}>", discord_role)),
+			)
+			.collect::<Vec<_>>();
+
+		let holders = if holders.is_empty() {
+			String::from("nobody")
+		} else {
+			holders.join(", ")
+		};
+		response.push_str(&format!("**{}**: {}\n", role.name(), holders));
+	}
+	let prefix = command_prefix(ctx, msg.guild_id).await;
+	response.push_str(&format!(
+		"\nChange them with `{}grant <role> @user` and `{}revoke <role> @user`.",
+		prefix, prefix
+	));
+
+	send_simple_message(&response, ctx, msg).await;
+
+	Ok(())
+}
+
+const ADMIN_LOG_PAGE_SIZE: i64 = 10;
+
+async fn build_admin_log_page(
+	pool: &SqlitePool,
+	guild: GuildId,
+	timezone: Tz,
+	page: i64,
+) -> anyhow::Result<EmbedPage> {
+	let total = adminlog::count_log(pool, guild).await?;
+	let pages = ((total + ADMIN_LOG_PAGE_SIZE - 1) / ADMIN_LOG_PAGE_SIZE).max(1);
+	let page = page.clamp(1, pages);
+
+	let entries = adminlog::get_log(
+		pool,
+		guild,
+		(page - 1) * ADMIN_LOG_PAGE_SIZE,
+		ADMIN_LOG_PAGE_SIZE,
+	)
+	.await?;
+
+	let response = if entries.is_empty() {
+		String::from("No privileged commands were run yet.")
+	} else {
+		entries
+			.iter()
+			.map(|entry| format_admin_log_entry(entry, timezone))
+			.collect::<Vec<_>>()
+			.join("\n")
+	};
+
+	let mut embed = CreateEmbed::default();
+	embed
+		.title("Admin Log")
+		.description(response)
+		.footer(|f| f.text(format!("Page {}/{}", page, pages)));
+
+	Ok(EmbedPage { embed, page, pages })
+}
+
+fn format_admin_log_entry(entry: &AdminLogEntry, timezone: Tz) -> String {
+	let arguments = if entry.arguments.is_empty() {
+		String::new()
+	} else {
+		format!(" {}", entry.arguments.replace('`', "'"))
+	};
+
+	format!(
+		"`#{}` {}\n<@{}> `{}{}`: {}",
+		entry.id,
+		format_date(entry.date, timezone),
+		entry.user_id,
+		entry.command,
+		arguments,
+		entry.outcome.name()
+	)
+}
+
+/// Pages through the privileged commands of the guild, newest first.
+pub async fn auditlog_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let account = *msg.author.id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+	let timezone = settings::get_timezone(&pool, account).await?;
+
+	let page = match msg.content.split_whitespace().nth(1) {
+		Some(word) => match word.parse::<i64>() {
+			Ok(page) => page,
+			Err(_) => {
+				send_usage(ctx, msg).await;
+				return Ok(());
+			}
+		},
+		None => 1,
+	};
+
+	let mut current = build_admin_log_page(&pool, guild, timezone, page).await?;
+
+	let mut message = msg
+		.channel_id
+		.send_message(&ctx.http, |m| {
+			m.set_embed(current.embed.clone());
+			if current.pages > 1 {
+				m.set_components(page_buttons("auditlog", current.page, current.pages, true));
+			}
+			m
+		})
+		.await?;
+
+	if current.pages <= 1 {
+		return Ok(());
+	}
+
+	while let Some(interaction) = message
+		.await_component_interaction(ctx)
+		.author_id(msg.author.id)
+		.timeout(PAGE_TIMEOUT)
+		.await
+	{
+		let page = match interaction.data.custom_id.as_str() {
+			"auditlog_previous" => current.page - 1,
+			"auditlog_next" => current.page + 1,
+			_ => current.page,
+		};
+
+		current = build_admin_log_page(&pool, guild, timezone, page).await?;
+		interaction
+			.create_interaction_response(&ctx.http, |r| {
+				r.kind(InteractionResponseType::UpdateMessage)
+					.interaction_response_data(|d| {
+						d.set_embed(current.embed.clone())
+							.set_components(page_buttons(
+								"auditlog",
+								current.page,
+								current.pages,
+								true,
+							))
+					})
+			})
+			.await
+			.ok();
+	}
+
+	message
+		.edit(&ctx.http, |m| {
+			m.set_components(page_buttons("auditlog", current.page, current.pages, false))
+		})
+		.await
+		.ok();
+
+	Ok(())
+}
+
+/// Sends a copy of `entry` to the log channel of its guild, if there is one.
+pub async fn publish_admin_log(ctx: &Context, entry: &AdminLogEntry) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	let channel = match guild::get_log_channel(&pool, entry.guild_id).await? {
+		Some(channel) => ChannelId(channel as u64),
+		None => return Ok(()),
+	};
+
+	channel
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| {
+				e.title("Admin Log")
+					.description(format_admin_log_entry(entry, Tz::UTC))
+			})
+		})
+		.await?;
+
+	Ok(())
+}
+
+/// Posts to the announcement channel of `guild`, if there is one.
+pub async fn announce(ctx: &Context, guild: GuildId, response: &str) {
+	let pool = database::get_pool(ctx).await;
+	let channel = match guild::get_announcement_channel(&pool, guild).await {
+		Ok(Some(channel)) => ChannelId(channel as u64),
+		Ok(None) => return,
+		Err(err) => {
+			println!("Error while announcing: {}", err);
 			return;
 		}
 	};
 
-	// Treat value.
-	let exp = CurrencyInfo::from(currency).subunitexp as f64;
-	let inter_value = f64::floor(value * f64::powf(10.0, -exp));
-	let integer_value: i64 = inter_value as i64;
+	if let Err(err) = channel
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| e.title("Announcement").description(response))
+		})
+		.await
+	{
+		println!("Error while announcing: {}", err);
+	}
+}
 
-	// Check if it is zero.
-	if integer_value == 0 {
-		send_simple_message("Please select a non-zero value.", ctx, msg).await;
-		return;
+/// Sets the channel receiving live copies of the admin log:
+/// `!logchannel #channel` or `!logchannel off`.
+pub async fn logchannel_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+
+	let channel = match msg.content.split_whitespace().nth(1) {
+		Some("off") => None,
+		Some(word) => match parse_channel_mention(word) {
+			Some(channel) => Some(channel),
+			None => {
+				send_usage(ctx, msg).await;
+				return Ok(());
+			}
+		},
+		None => {
+			let response = match guild::get_log_channel(&pool, guild).await? {
+				Some(channel) => format!("The admin log is copied to <#{}>.", channel),
+				None => format!(
+					"The admin log is not copied to any channel. Pick one with `{}logchannel #channel`.",
+					command_prefix(ctx, msg.guild_id).await
+				),
+			};
+			send_simple_message(&response, ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	guild::set_log_channel(&pool, guild, channel).await?;
+	let response = match channel {
+		Some(channel) => format!("The admin log is now copied to <#{}>.", channel),
+		None => String::from("The admin log is no longer copied to a channel."),
+	};
+	send_simple_message(&response, ctx, msg).await;
+
+	Ok(())
+}
+
+/// Describes where bank commands are allowed.
+fn describe_allowed_channels(channels: &[i64]) -> String {
+	if channels.is_empty() {
+		String::from("every channel")
+	} else {
+		channels
+			.iter()
+			.map(|channel| format!("<#{}>", channel))
+			.collect::<Vec<_>>()
+			.join(", ")
 	}
+}
 
-	// Create deposit.
-	match force_transfer(0, target_id, currency, integer_value).await {
-		Ok(_) => send_simple_message("**Central:** Operation Authorized.", ctx, msg).await,
-		Err(_) => send_simple_message("**Central:** Operation failed", ctx, msg).await,
+/// Shows or changes the settings of `guild`: `!config`, `!config prefix $`,
+/// `!config channels add #bank`, `!config announcements #news` and so on.
+pub async fn config_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	let words: Vec<&str> = msg.content.split_whitespace().skip(1).collect();
+
+	let response = match words.as_slice() {
+		[] => {
+			let prefix = guild::get_prefix(&pool, guild).await?;
+			let channels = guild::get_allowed_channels(&pool, guild).await?;
+			let describe_channel = |channel: Option<i64>| match channel {
+				Some(channel) => format!("<#{}>", channel),
+				None => String::from("none"),
+			};
+
+			format!(
+				"**Prefix:** `{}`\n**Bank commands allowed in:** {}\n**Announcements:** {}\n**Admin log:** {}\n\nSee `{}help config` to change them.",
+				prefix,
+				describe_allowed_channels(&channels),
+				describe_channel(guild::get_announcement_channel(&pool, guild).await?),
+				describe_channel(guild::get_log_channel(&pool, guild).await?),
+				prefix
+			)
+		}
+		["prefix", prefix] => {
+			if prefix.chars().count() > guild::MAX_PREFIX_LENGTH || prefix.contains('`') {
+				send_usage_error(
+					&format!(
+						"The prefix must have at most {} characters and no backticks.",
+						guild::MAX_PREFIX_LENGTH
+					),
+					ctx,
+					msg,
+				)
+				.await;
+				return Ok(());
+			}
+
+			let stored = if *prefix == guild::DEFAULT_PREFIX {
+				None
+			} else {
+				Some(*prefix)
+			};
+			guild::set_prefix(&pool, guild, stored).await?;
+			forget_prefix(ctx, guild).await;
+			format!(
+				"Bank commands now start with `{}`, such as `{}help`.",
+				prefix, prefix
+			)
+		}
+		["channels", "clear"] => {
+			guild::clear_allowed_channels(&pool, guild).await?;
+			String::from("Bank commands are now allowed in every channel.")
+		}
+		["channels", action @ ("add" | "remove"), mentions @ ..] if !mentions.is_empty() => {
+			let channels: Option<Vec<i64>> = mentions
+				.iter()
+				.map(|word| parse_channel_mention(word))
+				.collect();
+			let channels = match channels {
+				Some(channels) => channels,
+				None => {
+					send_usage(ctx, msg).await;
+					return Ok(());
+				}
+			};
+
+			for channel in channels {
+				if *action == "add" {
+					guild::allow_channel(&pool, guild, channel).await?;
+				} else {
+					guild::disallow_channel(&pool, guild, channel).await?;
+				}
+			}
+
+			let channels = guild::get_allowed_channels(&pool, guild).await?;
+			format!(
+				"Bank commands are now allowed in {}.",
+				describe_allowed_channels(&channels)
+			)
+		}
+		["announcements", "off"] => {
+			guild::set_announcement_channel(&pool, guild, None).await?;
+			String::from("Bank announcements are no longer posted.")
+		}
+		["announcements", word] => match parse_channel_mention(word) {
+			Some(channel) => {
+				guild::set_announcement_channel(&pool, guild, Some(channel)).await?;
+				format!("Bank announcements now go to <#{}>.", channel)
+			}
+			None => {
+				send_usage(ctx, msg).await;
+				return Ok(());
+			}
+		},
+		_ => {
+			send_usage(ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	msg.channel_id
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| e.title("Bank Settings").description(response))
+		})
+		.await?;
+
+	Ok(())
+}
+
+pub async fn transfer_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let mentions_vector = &msg.mentions;
+
+	if mentions_vector.is_empty() {
+		send_usage_error("Please ping the individual you want to transfer.", ctx, msg).await;
+		return Ok(());
+	}
+
+	if mentions_vector.len() > 1 {
+		send_simple_message(
+			"You cannot transfer multiple times. Only a single transfer is allowed.",
+			ctx,
+			msg,
+		)
+		.await;
+		return Ok(());
+	}
+
+	let (currency, integer_value, memo_start) = match parse_payment(guild, &msg.content) {
+		Ok(payment) => payment,
+		Err(response) => {
+			send_usage_error(&response, ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	let memo = parse_memo(&msg.content, memo_start);
+	make_transfer(
+		ctx,
+		&Invocation::Message(msg),
+		&mentions_vector[0],
+		currency,
+		integer_value,
+		memo,
+	)
+	.await
+}
+
+/// Transfers from the author of `inv` to `to`, asking for confirmation above
+/// the sender's threshold and holding it for the cancel window if there is one.
+pub async fn make_transfer(
+	ctx: &Context,
+	inv: &Invocation<'_>,
+	to: &User,
+	currency: Currency,
+	integer_value: i64,
+	memo: Option<String>,
+) -> anyhow::Result<()> {
+	if to.bot {
+		inv.say(ctx, "You cannot transfer to bots.").await;
+		return Ok(());
+	}
+
+	let to_account = *to.id.as_u64() as i64;
+	let from_account = *inv.author().id.as_u64() as i64;
+	if to_account == from_account {
+		inv.say(ctx, "You cannot transfer to yourself.").await;
+		return Ok(());
+	}
+
+	if CurrencyInfo::from(currency).retired {
+		inv.say(ctx, "This currency has been retired.").await;
+		return Ok(());
+	}
+
+	if integer_value <= 0 {
+		inv.say(ctx, "Please insert a positive value.").await;
+		return Ok(());
+	}
+
+	let pool = database::get_pool(ctx).await;
+	let money = Money::new(integer_value, currency);
+
+	// Large transfers are confirmed with buttons before anything is written.
+	if let Some(threshold) = settings::get_confirm_threshold(&pool, from_account).await? {
+		let threshold = threshold.saturating_mul(CurrencyInfo::from(currency).unit());
+		if integer_value >= threshold {
+			let mut summary = format!(
+				"Transfer {} to <@{}>?",
+				money.format(MoneyStyle::Name),
+				to_account
+			);
+			if let Some(memo) = memo.as_deref() {
+				summary.push_str(&format!("\nMemo: {}", memo));
+			}
+
+			if !ask_confirmation(ctx, inv, &summary).await? {
+				return Ok(());
+			}
+		}
+	}
+
+	let window = pending::get_cancel_window(ctx).await;
+	if window.is_zero() {
+		let transfer_status = send_transfer(
+			&pool,
+			from_account,
+			to_account,
+			currency,
+			integer_value,
+			memo.as_deref(),
+		)
+		.await;
+
+		inv.say(ctx, &transfer_status_message(transfer_status, money))
+			.await;
+		return Ok(());
+	}
+
+	// Early feedback only; the balance is checked again when the transfer settles.
+	if from_account != BANK_ID && get_balance(&pool, from_account, currency).await? < integer_value
+	{
+		inv.say(
+			ctx,
+			&transfer_status_message(TransferStatus::InsuficientBalance, money),
+		)
+		.await;
+		return Ok(());
+	}
+
+	let pending = pending::create_pending(
+		&pool,
+		PendingTransfer {
+			id: 0,
+			guild_id: currency.guild(),
+			currency: currency.code().to_owned(),
+			from_account,
+			to_account,
+			value: integer_value,
+			description: memo,
+			channel_id: *inv.channel_id().as_u64() as i64,
+			settle_date: get_current_time() + window.as_millis() as i64,
+		},
+	)
+	.await?;
+
+	let prefix = command_prefix(ctx, inv.guild_id()).await;
+	inv.say(
+		ctx,
+		&format!(
+			"Transfer of {} to <@{}> is pending and settles <t:{}:R>. Cancel it with `{}cancel {}`.",
+			money.format(MoneyStyle::Name),
+			to_account,
+			pending.settle_date / 1000,
+			prefix,
+			pending.id
+		),
+	)
+	.await;
+
+	schedule_settlement(ctx, pending);
+	Ok(())
+}
+
+fn transfer_status_message(status: TransferStatus, money: Money) -> Cow<'static, str> {
+	match status {
+		TransferStatus::Authorized(id) => format!(
+			"Transfer of {} authorized. Operation `#{}`.",
+			money.format(MoneyStyle::Name),
+			id
+		)
+		.into(),
+		TransferStatus::InsuficientBalance => "Insuficient balance for this transfer.".into(),
+		TransferStatus::BadValue => "Inserted value is bad.".into(),
+		TransferStatus::Retired => "This currency has been retired.".into(),
+		TransferStatus::Failed => "The transfer was not authorized, and blocked.".into(),
+	}
+}
+
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+fn confirm_buttons(enabled: bool) -> CreateComponents {
+	let mut components = CreateComponents::default();
+	components.create_action_row(|r| {
+		r.create_button(|b| {
+			b.custom_id("transfer_confirm")
+				.label("Confirm")
+				.style(ButtonStyle::Success)
+				.disabled(!enabled)
+		})
+		.create_button(|b| {
+			b.custom_id("transfer_cancel")
+				.label("Cancel")
+				.style(ButtonStyle::Danger)
+				.disabled(!enabled)
+		})
+	});
+
+	components
+}
+
+/// Shows `summary` with Confirm/Cancel buttons that only the author can press.
+/// Letting the buttons expire counts as cancelling.
+async fn ask_confirmation(
+	ctx: &Context,
+	inv: &Invocation<'_>,
+	summary: &str,
+) -> anyhow::Result<bool> {
+	let mut embed = CreateEmbed::default();
+	embed.description(summary);
+	let mut message = inv.send(ctx, embed, Some(confirm_buttons(true))).await?;
+
+	let interaction = message
+		.await_component_interaction(ctx)
+		.author_id(inv.author().id)
+		.timeout(CONFIRM_TIMEOUT)
+		.await;
+
+	let confirmed = matches!(&interaction, Some(interaction) if interaction.data.custom_id == "transfer_confirm");
+	let outcome = format!(
+		"{}\n{}",
+		summary,
+		if confirmed {
+			"Confirmed."
+		} else {
+			"Cancelled."
+		}
+	);
+
+	match interaction {
+		Some(interaction) => interaction
+			.create_interaction_response(&ctx.http, |r| {
+				r.kind(InteractionResponseType::UpdateMessage)
+					.interaction_response_data(|d| {
+						d.embed(|e| e.description(&outcome))
+							.set_components(confirm_buttons(false))
+					})
+			})
+			.await
+			.ok(),
+		None => message
+			.edit(&ctx.http, |m| {
+				m.embed(|e| e.description(&outcome))
+					.set_components(confirm_buttons(false))
+			})
+			.await
+			.ok(),
+	};
+
+	Ok(confirmed)
+}
+
+/// Settles `pending` once its cancel window closes and reports the outcome in
+/// the channel the transfer was requested from.
+pub fn schedule_settlement(ctx: &Context, pending: PendingTransfer) {
+	let ctx = ctx.clone();
+	tokio::spawn(async move {
+		let delay = (pending.settle_date - get_current_time()).max(0);
+		tokio::time::sleep(Duration::from_millis(delay as u64)).await;
+
+		let pool = database::get_pool(&ctx).await;
+		let status = match pending::settle_pending(&pool, pending.id).await {
+			Ok(Some(status)) => status,
+			Ok(None) => return,
+			Err(err) => {
+				println!(
+					"Error while settling pending transfer {}: {}",
+					pending.id, err
+				);
+				return;
+			}
+		};
+
+		let outcome = match Currency::try_from((pending.guild_id, pending.currency.as_str())) {
+			Ok(currency) => transfer_status_message(status, Money::new(pending.value, currency)),
+			Err(_) => format!(
+				"The currency `{}` no longer exists, so the transfer failed.",
+				pending.currency
+			)
+			.into(),
+		};
+		let response = format!(
+			"<@{}> Pending transfer `{}` to <@{}>: {}",
+			pending.from_account, pending.id, pending.to_account, outcome
+		);
+
+		ChannelId(pending.channel_id as u64)
+			.send_message(&ctx.http, |m| m.embed(|e| e.description(response)))
+			.await
+			.ok();
+	});
+}
+
+/// Picks up transfers that were still pending when the bot stopped.
+pub async fn resume_pending_transfers(ctx: &Context) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	for pending in pending::get_unsettled(&pool).await? {
+		schedule_settlement(ctx, pending);
+	}
+
+	Ok(())
+}
+
+pub async fn cancel_command(ctx: &Context, msg: &Message) -> anyhow::Result<()> {
+	let id = match msg
+		.content
+		.split_whitespace()
+		.nth(1)
+		.and_then(|word| word.trim_start_matches('#').parse::<u32>().ok())
+	{
+		Some(id) => id,
+		None => {
+			send_usage(ctx, msg).await;
+			return Ok(());
+		}
 	};
+
+	let pool = database::get_pool(ctx).await;
+	let account = *msg.author.id.as_u64() as i64;
+
+	let response = match pending::cancel_pending(&pool, id, account).await? {
+		CancelStatus::Cancelled => format!("Pending transfer `{}` cancelled.", id),
+		CancelStatus::NotFound => format!("Pending transfer `{}` does not exist.", id),
+		CancelStatus::NotSender => "Only the sender can cancel a pending transfer.".to_owned(),
+		CancelStatus::NotPending => {
+			format!("Pending transfer `{}` can no longer be cancelled.", id)
+		}
+	};
+
+	send_simple_message(&response, ctx, msg).await;
+	Ok(())
+}
+
+pub async fn confirm_command(ctx: &Context, msg: &Message) -> anyhow::Result<()> {
+	let account = *msg.author.id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+
+	let argument = match msg.content.split_whitespace().nth(1) {
+		Some(argument) => argument,
+		None => {
+			let prefix = command_prefix(ctx, msg.guild_id).await;
+			let response = match settings::get_confirm_threshold(&pool, account).await? {
+				Some(threshold) => format!(
+					"Transfers of {} units or more ask for confirmation. Turn it off with `{}confirm off`.",
+					threshold, prefix
+				),
+				None => format!(
+					"Transfers never ask for confirmation. Turn it on with `{}confirm 100`.",
+					prefix
+				),
+			};
+			send_simple_message(&response, ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	let threshold = if argument.eq_ignore_ascii_case("off") {
+		None
+	} else {
+		match argument.parse::<i64>() {
+			Ok(threshold) if threshold >= 0 => Some(threshold),
+			_ => {
+				send_usage(ctx, msg).await;
+				return Ok(());
+			}
+		}
+	};
+
+	settings::set_confirm_threshold(&pool, account, threshold).await?;
+	let response = match threshold {
+		Some(threshold) => format!(
+			"Transfers of {} units or more will now ask for confirmation.",
+			threshold
+		),
+		None => "Transfers will no longer ask for confirmation.".to_owned(),
+	};
+	send_simple_message(&response, ctx, msg).await;
+
+	Ok(())
+}
+
+pub async fn create_deposit_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+) -> anyhow::Result<Outcome> {
+	let mentions_vector = &msg.mentions;
+
+	if mentions_vector.len() >= 2 {
+		send_simple_message(
+			"You cannot transfer multiple times. Only a single transfer is allowed.",
+			ctx,
+			msg,
+		)
+		.await;
+		return Ok(Outcome::Failed);
+	}
+
+	// Find target.
+	let target = mentions_vector.first().unwrap_or(&msg.author);
+
+	// Find currency and value.
+	let split_iterator = msg.content.split_whitespace();
+
+	let mut currency: Option<Currency> = None;
+	let mut value: Option<&str> = None;
+	let mut memo_start = 0;
+
+	for (index, word) in split_iterator.enumerate() {
+		if currency.is_none() {
+			currency = Currency::try_from((guild, word)).ok();
+		}
+		if value.is_none() && currency::is_amount(word) {
+			value = Some(word);
+		}
+
+		if value.is_some() && currency.is_some() {
+			memo_start = index + 1;
+			break;
+		}
+	}
+
+	let currency = match currency {
+		Some(currency) => currency,
+		None => {
+			send_usage_error(
+				"Currency has not been detected. Specify a currency.",
+				ctx,
+				msg,
+			)
+			.await;
+			return Ok(Outcome::Failed);
+		}
+	};
+
+	let value = match value {
+		Some(value) => value,
+		None => {
+			send_usage_error(
+				"A value has not been detected. Specify which value to transfer.",
+				ctx,
+				msg,
+			)
+			.await;
+			return Ok(Outcome::Failed);
+		}
+	};
+
+	// Treat value.
+	let integer_value = match currency::parse_amount(value, currency) {
+		Ok(integer_value) => integer_value,
+		Err(err) => {
+			send_simple_message(&err.to_string(), ctx, msg).await;
+			return Ok(Outcome::Failed);
+		}
+	};
+
+	let memo = parse_memo(&msg.content, memo_start);
+	make_deposit(
+		ctx,
+		&Invocation::Message(msg),
+		target,
+		currency,
+		integer_value,
+		memo,
+	)
+	.await
+}
+
+/// Deposits into the account of `target` or, for negative values, charges it.
+/// The outcome is `Failed` when the operation was refused or did not go through.
+pub async fn make_deposit(
+	ctx: &Context,
+	inv: &Invocation<'_>,
+	target: &User,
+	currency: Currency,
+	integer_value: i64,
+	memo: Option<String>,
+) -> anyhow::Result<Outcome> {
+	if target.bot {
+		inv.say(ctx, "You cannot transfer to bots.").await;
+		return Ok(Outcome::Failed);
+	}
+
+	if CurrencyInfo::from(currency).retired {
+		inv.say(ctx, "This currency has been retired.").await;
+		return Ok(Outcome::Failed);
+	}
+
+	// Check if it is zero.
+	if integer_value == 0 {
+		inv.say(ctx, "Please select a non-zero value.").await;
+		return Ok(Outcome::Failed);
+	}
+
+	// Create deposit.
+	let pool = database::get_pool(ctx).await;
+	let target_id = *target.id.as_u64() as i64;
+	match force_transfer(
+		&pool,
+		BANK_ID,
+		target_id,
+		currency,
+		integer_value,
+		memo.as_deref(),
+	)
+	.await
+	{
+		Ok(id) => {
+			inv.say(
+				ctx,
+				&format!("**Central:** Operation Authorized. Operation `#{}`.", id),
+			)
+			.await;
+			Ok(Outcome::Done)
+		}
+		Err(_) => {
+			inv.say(ctx, "**Central:** Operation failed").await;
+			Ok(Outcome::Failed)
+		}
+	}
+}
+
+fn parse_subunitexp(word: &str) -> Result<i32, String> {
+	match word.parse() {
+		Ok(subunitexp) if currency::SUBUNITEXP_RANGE.contains(&subunitexp) => Ok(subunitexp),
+		_ => Err(format!(
+			"The subunit exponent must be an integer from {} to {}, such as `-2`.",
+			currency::SUBUNITEXP_RANGE.start(),
+			currency::SUBUNITEXP_RANGE.end()
+		)),
+	}
+}
+
+/// Parses the `!currency` fields that can be edited, such as `name` or
+/// `subunitexp`, into `info`.
+fn edit_currency_field(
+	info: &mut CurrencyInfo<'_>,
+	field: &str,
+	value: &str,
+) -> Result<(), String> {
+	match field.to_lowercase().as_str() {
+		"name" => info.name = value.to_owned().into(),
+		"prefix" => info.prefix = value.to_owned().into(),
+		"posfix" => info.posfix = value.to_owned().into(),
+		"picture" => info.picture = value.to_owned().into(),
+		"subunitexp" => info.subunitexp = parse_subunitexp(value)?,
+		_ => {
+			return Err(String::from(
+				"Editable fields are `name`, `prefix`, `posfix`, `picture` and `subunitexp`.",
+			))
+		}
+	}
+
+	Ok(())
+}
+
+pub async fn currency_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	let words: Vec<&str> = msg.content.split_whitespace().skip(1).collect();
+
+	let response: String = match words.as_slice() {
+		[] | ["list"] => {
+			let mut response = String::new();
+			for currency in currency::all_currencies(guild) {
+				let info = CurrencyInfo::from(currency);
+				response.push_str(&format!(
+					"`{}` {} ({}, {}, 10^{}){}\n",
+					info.code,
+					info.name,
+					info.prefix,
+					info.posfix,
+					info.subunitexp,
+					if info.retired { " **retired**" } else { "" }
+				));
+			}
+
+			if response.is_empty() {
+				String::from("There are no currencies.")
+			} else {
+				response
+			}
+		}
+		["create", code, subunitexp, prefix, posfix, picture, name @ ..] if !name.is_empty() => {
+			let subunitexp = match parse_subunitexp(subunitexp) {
+				Ok(subunitexp) => subunitexp,
+				Err(response) => {
+					send_simple_message(&response, ctx, msg).await;
+					return Ok(());
+				}
+			};
+
+			let info = CurrencyInfo {
+				guild,
+				code: code.to_uppercase().into(),
+				prefix: (*prefix).into(),
+				posfix: (*posfix).into(),
+				name: name.join(" ").into(),
+				picture: (*picture).into(),
+				subunitexp,
+				retired: false,
+			};
+
+			match currency::create_currency(&pool, &info).await {
+				Ok(_) => {
+					announce(
+						ctx,
+						guild,
+						&format!("A new currency is out: {} (`{}`).", info.name, info.code),
+					)
+					.await;
+					format!("**Central:** Currency `{}` created.", info.code)
+				}
+				Err(err) => format!("**Central:** Could not create the currency: {}", err),
+			}
+		}
+		["edit", code, field, value @ ..] if !value.is_empty() => {
+			let mut info = match Currency::try_from((guild, *code)) {
+				Ok(currency) => CurrencyInfo::from(currency),
+				Err(_) => {
+					send_simple_message("No matching currency.", ctx, msg).await;
+					return Ok(());
+				}
+			};
+
+			if let Err(response) = edit_currency_field(&mut info, field, &value.join(" ")) {
+				send_simple_message(&response, ctx, msg).await;
+				return Ok(());
+			}
+
+			match currency::update_currency(&pool, &info).await {
+				Ok(_) => format!("**Central:** Currency `{}` updated.", info.code),
+				Err(err) => format!("**Central:** Could not update the currency: {}", err),
+			}
+		}
+		[action @ ("retire" | "restore"), code] => {
+			let mut info = match Currency::try_from((guild, *code)) {
+				Ok(currency) => CurrencyInfo::from(currency),
+				Err(_) => {
+					send_simple_message("No matching currency.", ctx, msg).await;
+					return Ok(());
+				}
+			};
+
+			info.retired = *action == "retire";
+			currency::update_currency(&pool, &info).await?;
+
+			let announcement = if info.retired {
+				format!(
+					"{} (`{}`) is retired and can no longer be transferred.",
+					info.name, info.code
+				)
+			} else {
+				format!("{} (`{}`) is back in circulation.", info.name, info.code)
+			};
+			announce(ctx, guild, &announcement).await;
+			format!("**Central:** Currency `{}` {}d.", info.code, action)
+		}
+		_ => {
+			send_usage(ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	send_simple_message(&response, ctx, msg).await;
+
+	// The slash commands offer the currencies as choices.
+	if !matches!(words.as_slice(), [] | ["list"]) {
+		if let Err(err) = slash::register_commands(ctx, guild).await {
+			println!("Error while registering slash commands: {}", err);
+		}
+	}
+
+	Ok(())
+}
+
+pub async fn rebuild_balances_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+
+	match rebuild_balances(&pool, guild).await {
+		Ok(_) => {
+			send_simple_message("**Central:** Balances rebuilt from the ledger.", ctx, msg).await
+		}
+		Err(_) => send_simple_message("**Central:** Operation failed", ctx, msg).await,
+	};
+
+	Ok(())
+}
+
+const AUDIT_ISSUES_SHOWN: usize = 10;
+
+pub async fn audit_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	let report = audit::verify_ledger(&pool, guild).await?;
+
+	let mut response = format!("Checked {} ledger rows.\n", report.rows_checked);
+	if report.issues.is_empty() {
+		response.push_str("No issues found.");
+	} else {
+		for issue in report.issues.iter().take(AUDIT_ISSUES_SHOWN) {
+			response.push_str(&format!("- {}\n", issue));
+		}
+
+		if report.issues.len() > AUDIT_ISSUES_SHOWN {
+			response.push_str(&format!(
+				"...and {} more. Run `bankbot audit` for the full report.",
+				report.issues.len() - AUDIT_ISSUES_SHOWN
+			));
+		}
+	}
+
+	msg.channel_id
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| e.title("Ledger Audit").description(response))
+		})
+		.await
+		.ok();
+
+	Ok(())
+}
+
+/// Posts the current ledger head hash, so the community can later check that
+/// the history up to it was not rewritten. Goes to the mentioned channel, the
+/// announcement channel or the current one.
+pub async fn publish_head_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+
+	let channel = match msg.content.split_whitespace().nth(1) {
+		Some(word) => parse_channel_mention(word),
+		None => guild::get_announcement_channel(&pool, guild).await?,
+	};
+	let channel_id = channel
+		.map(|channel| ChannelId(channel as u64))
+		.unwrap_or(msg.channel_id);
+
+	let response = match chain::get_head(&pool, guild).await? {
+		Some(chain::ChainHead {
+			id,
+			hash: Some(hash),
+			date,
+		}) => format!(
+			"Ledger head: transfer #{} at <t:{}:f>\n`{}`",
+			id,
+			date / 1000,
+			hash
+		),
+		Some(head) => {
+			let prefix = command_prefix(ctx, msg.guild_id).await;
+			send_simple_message(
+				&format!(
+					"Transfer #{}, the ledger head, is unsealed, so there is no hash to publish. Run `{}audit`.",
+					head.id, prefix
+				),
+				ctx,
+				msg,
+			)
+			.await;
+			return Ok(());
+		}
+		None => String::from("The ledger is empty."),
+	};
+
+	channel_id
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| e.title("Ledger Head").description(response))
+		})
+		.await?;
+
+	Ok(())
+}
+
+pub async fn reverse_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+) -> anyhow::Result<Outcome> {
+	let mut words = msg.content.split_whitespace().skip(1);
+
+	let id = match words
+		.next()
+		.map(|word| word.trim_start_matches('#').parse::<u32>())
+	{
+		Some(Ok(id)) => id,
+		_ => {
+			send_usage(ctx, msg).await;
+			return Ok(Outcome::Failed);
+		}
+	};
+
+	let mut words = words.peekable();
+	let force = words
+		.next_if(|word| word.eq_ignore_ascii_case("force"))
+		.is_some();
+	let memo = parse_memo(&words.collect::<Vec<_>>().join(" "), 0);
+
+	let pool = database::get_pool(ctx).await;
+	let prefix = command_prefix(ctx, msg.guild_id).await;
+	let status = reverse_transfer(&pool, guild, id, force, memo.as_deref()).await;
+	let outcome = match status {
+		ReversalStatus::Reversed(_) => Outcome::Done,
+		_ => Outcome::Failed,
+	};
+	let response = match status {
+		ReversalStatus::Reversed(reversal_id) => format!(
+			"**Central:** Operation #{} reversed by operation #{}.",
+			id, reversal_id
+		),
+		ReversalStatus::NotFound => format!("**Central:** Operation #{} does not exist.", id),
+		ReversalStatus::AlreadyReversed => {
+			format!("**Central:** Operation #{} was already reversed.", id)
+		}
+		ReversalStatus::IsReversal => {
+			format!("**Central:** Operation #{} is itself a reversal.", id)
+		}
+		ReversalStatus::IsExchange => format!(
+			"**Central:** Operation #{} is part of an exchange and cannot be reversed alone.",
+			id
+		),
+		ReversalStatus::InsuficientBalance => format!(
+			"**Central:** The recipient no longer holds the funds of operation #{}. Use `{}reverse {} force` to reverse it anyway.",
+			id, prefix, id
+		),
+		ReversalStatus::Failed => String::from("**Central:** Operation failed"),
+	};
+
+	send_simple_message(&response, ctx, msg).await;
+	Ok(outcome)
+}
+
+fn describe_account(account: i64) -> String {
+	if account == BANK_ID {
+		String::from("Central Bank")
+	} else {
+		format!("<@!{}>", account)
+	}
+}
+
+/// Shows the full detail of one operation to its sender, its receiver or
+/// someone allowed to view any receipt.
+pub async fn receipt_command(
+	ctx: &Context,
+	msg: &Message,
+	guild: GuildId,
+	view_any: bool,
+) -> anyhow::Result<()> {
+	let id = match msg
+		.content
+		.split_whitespace()
+		.nth(1)
+		.map(|word| word.trim_start_matches('#').parse::<u32>())
+	{
+		Some(Ok(id)) => id,
+		_ => {
+			send_usage(ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	let account = *msg.author.id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+
+	let row = match get_transfer(&pool, guild, id).await? {
+		Some(row) if view_any || row.from_account == account || row.to_account == account => row,
+		_ => {
+			send_simple_message(&format!("Operation `#{}` was not found.", id), ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	let currency = Currency::try_from((row.guild_id, row.currency.as_str()))?;
+	let info = CurrencyInfo::from(currency);
+	let timezone = settings::get_timezone(&pool, account).await?;
+
+	let mut response = format!(
+		"From: {}\nTo: {}\nAmount: `{}`\nDate: {}\n",
+		describe_account(row.from_account),
+		describe_account(row.to_account),
+		Money::new(row.value, currency),
+		format_date(row.transfer_date, timezone)
+	);
+
+	if let Some(description) = &row.description {
+		response.push_str(&format!("Memo: `{}`\n", description));
+	}
+
+	if row.from_account == account {
+		response.push_str(&format!(
+			"Your balance after: `{}`\n",
+			Money::new(row.from_balance, currency)
+		));
+	} else if row.to_account == account {
+		response.push_str(&format!(
+			"Your balance after: `{}`\n",
+			Money::new(row.to_balance, currency)
+		));
+	} else {
+		response.push_str(&format!(
+			"Sender balance after: `{}`\nReceiver balance after: `{}`\n",
+			Money::new(row.from_balance, currency),
+			Money::new(row.to_balance, currency)
+		));
+	}
+
+	if let Some(reverses) = row.reverses {
+		response.push_str(&format!("Reversal of operation `#{}`.\n", reverses));
+	}
+	if let Some(exchange_id) = row.exchange_id {
+		if let Some(record) = exchange::get_exchange(&pool, exchange_id).await? {
+			response.push_str(&format!("Exchanged at {}.\n", record.describe()));
+		}
+	}
+	if let Some(reversed_by) = row.reversed_by {
+		response.push_str(&format!("**Reversed** by operation `#{}`.\n", reversed_by));
+	}
+
+	msg.channel_id
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| {
+				e.title(format!("Receipt #{}: {}", row.id, info.name))
+					.description(response)
+					.thumbnail(info.picture)
+			})
+		})
+		.await
+		.ok();
+
+	Ok(())
+}
+
+fn invoice_buttons(id: u32, enabled: bool) -> CreateComponents {
+	let mut components = CreateComponents::default();
+	components.create_action_row(|r| {
+		r.create_button(|b| {
+			b.custom_id(format!("invoice_pay:{}", id))
+				.label("Pay")
+				.style(ButtonStyle::Success)
+				.disabled(!enabled)
+		})
+		.create_button(|b| {
+			b.custom_id(format!("invoice_decline:{}", id))
+				.label("Decline")
+				.style(ButtonStyle::Danger)
+				.disabled(!enabled)
+		})
+	});
+
+	components
+}
+
+/// `!request @user <amount> <currency> [memo] [until YYYY-MM-DD]` sends an
+/// invoice that the mentioned user can pay or decline with buttons.
+pub async fn request_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let mentions_vector = &msg.mentions;
+
+	if mentions_vector.len() != 1 {
+		send_usage_error("Please ping the individual you want to bill.", ctx, msg).await;
+		return Ok(());
+	}
+
+	if mentions_vector[0].bot {
+		send_simple_message("You cannot bill bots.", ctx, msg).await;
+		return Ok(());
+	}
+
+	let payer = *mentions_vector[0].id.as_u64() as i64;
+	let requester = *msg.author.id.as_u64() as i64;
+	if payer == requester {
+		send_simple_message("You cannot bill yourself.", ctx, msg).await;
+		return Ok(());
+	}
+
+	let (currency, integer_value, memo_start) = match parse_payment(guild, &msg.content) {
+		Ok(payment) => payment,
+		Err(response) => {
+			send_usage_error(&response, ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	let pool = database::get_pool(ctx).await;
+	let timezone = settings::get_timezone(&pool, requester).await?;
+
+	// `until <date>` may appear anywhere in the memo; the invoice stays open
+	// through the end of that day.
+	let words: Vec<&str> = msg.content.split_whitespace().skip(memo_start).collect();
+	let mut expiry_date = None;
+	let mut memo_words = vec![];
+	let mut index = 0;
+	while index < words.len() {
+		if words[index] == "until" {
+			let date = words
+				.get(index + 1)
+				.and_then(|word| parse_local_midnight(word, timezone, 1));
+			if date.is_some() {
+				expiry_date = date;
+				index += 2;
+				continue;
+			}
+		}
+
+		memo_words.push(words[index]);
+		index += 1;
+	}
+
+	let now = get_current_time();
+	let expiry_date =
+		expiry_date.unwrap_or(now + DEFAULT_INVOICE_EXPIRY_DAYS as i64 * 24 * 60 * 60 * 1000);
+	if expiry_date <= now {
+		send_simple_message("The expiry date has already passed.", ctx, msg).await;
+		return Ok(());
+	}
+
+	let memo = parse_memo(&memo_words.join(" "), 0);
+	let id = invoice::create_invoice(
+		&pool,
+		requester,
+		payer,
+		currency,
+		integer_value,
+		memo.as_deref(),
+		expiry_date,
+	)
+	.await?;
+
+	let info = CurrencyInfo::from(currency);
+	let mut response = format!(
+		"{} requests {} from {}.\n",
+		describe_account(requester),
+		Money::new(integer_value, currency).format(MoneyStyle::Name),
+		describe_account(payer)
+	);
+	if let Some(memo) = &memo {
+		response.push_str(&format!("Memo: `{}`\n", memo));
+	}
+	response.push_str(&format!(
+		"Expires: {}\n",
+		format_date(expiry_date, timezone)
+	));
+
+	msg.channel_id
+		.send_message(&ctx.http, |m| {
+			m.content(describe_account(payer))
+				.embed(|e| {
+					e.title(format!("Invoice #{}", id))
+						.description(response)
+						.thumbnail(info.picture)
+				})
+				.set_components(invoice_buttons(id, true))
+		})
+		.await?;
+
+	Ok(())
+}
+
+/// Handles the Pay/Decline buttons of an invoice. The invoice id travels in the
+/// button id, so the buttons keep working after a restart.
+pub async fn invoice_interaction(
+	ctx: &Context,
+	interaction: &MessageComponentInteraction,
+) -> anyhow::Result<()> {
+	let (action, id) = match interaction.data.custom_id.split_once(':') {
+		Some((action, id)) => (action, id.parse::<u32>()?),
+		None => return Ok(()),
+	};
+
+	let pool = database::get_pool(ctx).await;
+	let payer = *interaction.user.id.as_u64() as i64;
+	let status = match action {
+		"invoice_pay" => invoice::pay_invoice(&pool, id, payer).await,
+		"invoice_decline" => invoice::decline_invoice(&pool, id, payer).await,
+		_ => return Ok(()),
+	};
+
+	let outcome: Cow<'_, str> = match status {
+		InvoiceStatus::Paid(transfer_id) => {
+			format!("**Paid** by operation `#{}`.", transfer_id).into()
+		}
+		InvoiceStatus::Declined => "**Declined.**".into(),
+		InvoiceStatus::Closed => "This invoice is no longer open.".into(),
+		InvoiceStatus::Expired => "**Expired.**".into(),
+		InvoiceStatus::NotFound | InvoiceStatus::NotPayer => {
+			return reply_ephemeral(ctx, interaction, "This invoice is not addressed to you.")
+				.await;
+		}
+		InvoiceStatus::InsuficientBalance => {
+			return reply_ephemeral(ctx, interaction, "Insuficient balance to pay this invoice.")
+				.await;
+		}
+		InvoiceStatus::Retired => {
+			return reply_ephemeral(
+				ctx,
+				interaction,
+				"The currency of this invoice has been retired, so it cannot be paid.",
+			)
+			.await;
+		}
+		InvoiceStatus::Failed => {
+			return reply_ephemeral(
+				ctx,
+				interaction,
+				"The payment was not authorized, and blocked.",
+			)
+			.await;
+		}
+	};
+
+	let (mut embed, description) = match interaction.message.embeds.first() {
+		Some(embed) => (
+			CreateEmbed::from(embed.clone()),
+			embed.description.clone().unwrap_or_default(),
+		),
+		None => (CreateEmbed::default(), String::new()),
+	};
+	embed.description(format!("{}{}", description, outcome));
+
+	interaction
+		.create_interaction_response(&ctx.http, |r| {
+			r.kind(InteractionResponseType::UpdateMessage)
+				.interaction_response_data(|d| {
+					d.set_embed(embed)
+						.set_components(invoice_buttons(id, false))
+				})
+		})
+		.await?;
+
+	Ok(())
+}
+
+async fn reply_ephemeral(
+	ctx: &Context,
+	interaction: &MessageComponentInteraction,
+	response: &str,
+) -> anyhow::Result<()> {
+	interaction
+		.create_interaction_response(&ctx.http, |r| {
+			r.kind(InteractionResponseType::ChannelMessageWithSource)
+				.interaction_response_data(|d| d.content(response).ephemeral(true))
+		})
+		.await?;
+
+	Ok(())
+}
+
+/// Lists the open invoices the author sent and the ones they have to pay.
+pub async fn invoices_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let account = *msg.author.id.as_u64() as i64;
+	let pool = database::get_pool(ctx).await;
+	let timezone = settings::get_timezone(&pool, account).await?;
+
+	let mut received = String::new();
+	let mut sent = String::new();
+	for invoice in invoice::get_open_invoices(&pool, guild, account).await? {
+		let money = Money::new(
+			invoice.value,
+			Currency::try_from((invoice.guild_id, invoice.currency.as_str()))?,
+		);
+		let line = if invoice.payer == account {
+			format!(
+				"`#{}` `{}` to {}",
+				invoice.id,
+				money,
+				describe_account(invoice.requester)
+			)
+		} else {
+			format!(
+				"`#{}` `{}` from {}",
+				invoice.id,
+				money,
+				describe_account(invoice.payer)
+			)
+		};
+
+		let target = if invoice.payer == account {
+			&mut received
+		} else {
+			&mut sent
+		};
+		target.push_str(&format!(
+			"{}, expires {}\n",
+			line,
+			format_date(invoice.expiry_date, timezone)
+		));
+		if let Some(description) = &invoice.description {
+			target.push_str(&format!("Memo: `{}`\n", description));
+		}
+	}
+
+	if received.is_empty() {
+		received = String::from("Nothing to pay.");
+	}
+	if sent.is_empty() {
+		sent = String::from("No open requests.");
+	}
+
+	msg.channel_id
+		.send_message(&ctx.http, |m| {
+			m.embed(|e| {
+				e.title("Open invoices")
+					.field("To pay", received, false)
+					.field("Requested", sent, false)
+			})
+		})
+		.await
+		.ok();
+
+	Ok(())
+}
+
+/// `!exchange [amount] <from> <to>` quotes an exchange and, when an amount is
+/// given, runs it once the quote is confirmed.
+pub async fn exchange_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let currencies: Vec<Currency> = msg
+		.content
+		.split_whitespace()
+		.filter_map(|word| Currency::try_from((guild, word)).ok())
+		.collect();
+
+	let (from, to) = match currencies[..] {
+		[from, to] if from != to => (from, to),
+		_ => {
+			send_usage(ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	if CurrencyInfo::from(from).retired || CurrencyInfo::from(to).retired {
+		send_simple_message("This currency has been retired.", ctx, msg).await;
+		return Ok(());
+	}
+
+	let amount = msg
+		.content
+		.split_whitespace()
+		.find(|word| currency::is_amount(word));
+	let value = match amount.map(|word| currency::parse_amount(word, from)) {
+		Some(Ok(value)) if value > 0 => value,
+		Some(Ok(_)) => {
+			send_simple_message("Please insert a positive value.", ctx, msg).await;
+			return Ok(());
+		}
+		Some(Err(err)) => {
+			send_simple_message(&err.to_string(), ctx, msg).await;
+			return Ok(());
+		}
+		None => CurrencyInfo::from(from).unit(),
+	};
+
+	let pool = database::get_pool(ctx).await;
+	let spread = exchange::get_spread(ctx).await;
+	let quote = match exchange::quote(&pool, from, to, value, spread).await? {
+		Some(quote) => quote,
+		None => {
+			send_simple_message(
+				&format!(
+					"There is no exchange rate between `{}` and `{}`.",
+					from.code(),
+					to.code()
+				),
+				ctx,
+				msg,
+			)
+			.await;
+			return Ok(());
+		}
+	};
+
+	let mut summary = format!(
+		"`{}` → `{}`\nRate: 1 {} = {} {}",
+		Money::new(quote.value, from),
+		Money::new(quote.credit, to),
+		from.code(),
+		exchange::format_rate(quote.rate),
+		to.code()
+	);
+	if spread > 0 {
+		summary.push_str(&format!("\nSpread: {}", exchange::format_spread(spread)));
+	}
+
+	if amount.is_none() {
+		send_simple_message(&summary, ctx, msg).await;
+		return Ok(());
+	}
+
+	if quote.credit <= 0 {
+		send_simple_message("This amount is too small to exchange.", ctx, msg).await;
+		return Ok(());
+	}
+
+	if !ask_confirmation(ctx, &Invocation::Message(msg), &summary).await? {
+		return Ok(());
+	}
+
+	let account = *msg.author.id.as_u64() as i64;
+	let response: Cow<'_, str> = match exchange::exchange(&pool, account, quote).await {
+		ExchangeStatus::Exchanged(debit_id, credit_id) => format!(
+			"Exchanged `{}` for `{}`. Operations `#{}` and `#{}`.",
+			Money::new(quote.value, from),
+			Money::new(quote.credit, to),
+			debit_id,
+			credit_id
+		)
+		.into(),
+		ExchangeStatus::NoRate => "There is no exchange rate for these currencies anymore.".into(),
+		ExchangeStatus::RateChanged => {
+			"The rate changed before the exchange ran. Request a new quote.".into()
+		}
+		ExchangeStatus::TooSmall => "This amount is too small to exchange.".into(),
+		ExchangeStatus::InsuficientBalance => "Insuficient balance for this exchange.".into(),
+		ExchangeStatus::Failed => "The exchange was not authorized, and blocked.".into(),
+	};
+
+	send_simple_message(&response, ctx, msg).await;
+	Ok(())
+}
+
+/// Director command maintaining the exchange rates: `!rate`, `!rate refresh`,
+/// `!rate <code> <rate>` and `!rate <code> off`.
+pub async fn rate_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let pool = database::get_pool(ctx).await;
+	let arguments: Vec<&str> = msg.content.split_whitespace().skip(1).collect();
+
+	let response: Cow<'_, str> = match arguments[..] {
+		[] => {
+			let mut response = String::new();
+			for (code, rate, source) in exchange::get_rates(&pool, guild).await? {
+				response.push_str(&format!(
+					"`{}` {} ({})\n",
+					code,
+					exchange::format_rate(rate),
+					source
+				));
+			}
+
+			if response.is_empty() {
+				"No exchange rates are set.".into()
+			} else {
+				response.into()
+			}
+		}
+		["refresh"] => match exchange::get_rate_provider(ctx).await {
+			Some(provider) => {
+				let updated =
+					exchange::refresh_rates(&pool, provider.as_ref(), Some(guild)).await?;
+				format!("Updated {} rates from `{}`.", updated, provider.name()).into()
+			}
+			None => "No rate provider is configured.".into(),
+		},
+		[code, value] => match Currency::try_from((guild, code)) {
+			Ok(currency) if value == "off" => {
+				if exchange::remove_rate(&pool, currency).await? {
+					format!("`{}` can no longer be exchanged.", currency.code()).into()
+				} else {
+					format!("`{}` has no rate.", currency.code()).into()
+				}
+			}
+			Ok(currency) => match exchange::parse_rate(value) {
+				Some(rate) => {
+					exchange::set_rate(&pool, currency, rate, exchange::DIRECTOR_SOURCE).await?;
+					format!(
+						"One `{}` is now worth {} reference units.",
+						currency.code(),
+						exchange::format_rate(rate)
+					)
+					.into()
+				}
+				None => "Rates must be positive decimals such as `1.08`.".into(),
+			},
+			Err(_) => format!("Unknown currency `{}`.", code).into(),
+		},
+		_ => {
+			send_usage(ctx, msg).await;
+			return Ok(());
+		}
+	};
+
+	send_simple_message(&response, ctx, msg).await;
+	Ok(())
+}
+
+const DAY_MILLIS: i64 = 24 * 60 * 60 * 1000;
+const RATE_SERIES_POINTS: i64 = 30;
+
+/// `!rates <code>` shows the current cross rates of a currency with their 24h
+/// and 7d change, and `!rates <from> <to> [days]d` the history of one pair.
+pub async fn rates_command(ctx: &Context, msg: &Message, guild: GuildId) -> anyhow::Result<()> {
+	let currencies: Vec<Currency> = msg
+		.content
+		.split_whitespace()
+		.filter_map(|word| Currency::try_from((guild, word)).ok())
+		.collect();
+	let days = msg
+		.content
+		.split_whitespace()
+		.find_map(|word| word.strip_suffix('d')?.parse::<i64>().ok());
+
+	let pool = database::get_pool(ctx).await;
+	let now = get_current_time();
+
+	match (&currencies[..], days) {
+		([base], None) => {
+			let mut response = String::new();
+			for other in currency::active_currencies(guild) {
+				if other == *base {
+					continue;
+				}
+
+				// The history keeps removed rates, so it only gives the changes.
+				let current = match exchange::get_cross_rate(&pool, *base, other).await? {
+					Some(current) => current,
+					None => continue,
+				};
+
+				let mut line = format!("`{}` {}", other.code(), exchange::format_rate(current));
+				for (label, days) in [("24h", 1), ("7d", 7)] {
+					let before =
+						exchange::get_cross_rate_at(&pool, *base, other, now - days * DAY_MILLIS)
+							.await?;
+					let change = match before {
+						Some(before) => exchange::format_change(before, current),
+						None => String::from("n/a"),
+					};
+					line.push_str(&format!(" | {} `{}`", label, change));
+				}
+
+				response.push_str(&line);
+				response.push('\n');
+			}
+
+			if response.is_empty() {
+				response = format!("There are no exchange rates for `{}`.", base.code());
+			}
+
+			msg.channel_id
+				.send_message(&ctx.http, |m| {
+					m.embed(|e| {
+						e.title(format!("1 {} in other currencies", base.code()))
+							.description(response)
+					})
+				})
+				.await
+				.ok();
+		}
+		([from, to], days) if from != to => {
+			let days = days.unwrap_or(30).clamp(1, 365);
+			let timezone = settings::get_timezone(&pool, *msg.author.id.as_u64() as i64).await?;
+			let step = ((days + RATE_SERIES_POINTS - 1) / RATE_SERIES_POINTS).max(1);
+
+			let mut response = String::new();
+			let mut day = days;
+			while day >= 0 {
+				let date = now - day * DAY_MILLIS;
+				if let Some(rate) = exchange::get_cross_rate_at(&pool, *from, *to, date).await? {
+					let local = Utc
+						.timestamp_millis_opt(date)
+						.single()
+						.map(|utc| utc.with_timezone(&timezone).format("%Y-%m-%d").to_string())
+						.unwrap_or_default();
+					response.push_str(&format!("`{}` {}\n", local, exchange::format_rate(rate)));
+				}
+
+				day -= step;
+			}
+
+			if response.is_empty() {
+				response = format!(
+					"There is no rate history between `{}` and `{}`.",
+					from.code(),
+					to.code()
+				);
+			}
+
+			msg.channel_id
+				.send_message(&ctx.http, |m| {
+					m.embed(|e| {
+						e.title(format!(
+							"1 {} in {}, last {} days",
+							from.code(),
+							to.code(),
+							days
+						))
+						.description(response)
+					})
+				})
+				.await
+				.ok();
+		}
+		_ => {
+			send_usage(ctx, msg).await;
+		}
+	}
+
+	Ok(())
 }
@@ -1,130 +1,658 @@
+use sqlx::{SqliteConnection, SqlitePool};
 use std::borrow::Cow;
+use std::ops::RangeInclusive;
+use std::sync::RwLock;
 
-macro_rules! generate_currency {
-	($enum_name:ident { $($elements:ident),* }, $array_name:ident) => {
-		#[derive(Debug, Clone, Copy)]
-		pub enum $enum_name {
-			$($elements),*
-		}
+use crate::operation::GuildId;
 
-		pub const $array_name: &[$enum_name] = &[
-			$($enum_name::$elements),*
-		];
-	};
+/// Guild whose currencies are templates copied into every new guild.
+pub const TEMPLATE_GUILD: GuildId = 0;
+
+/// Subunit exponents a currency may have, from whole units down to nine
+/// decimal places. Finer ones overflow `CurrencyInfo::unit`.
+pub const SUBUNITEXP_RANGE: RangeInclusive<i32> = -9..=0;
+
+/// A currency from the registry, identified by its guild and its three letter
+/// code.
+///
+/// Only the key is kept so the handle stays `Copy`; everything else is looked
+/// up in the registry through `CurrencyInfo::from`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Currency {
+	guild: GuildId,
+	code: [u8; 3],
 }
 
-generate_currency!(
-	Currency {
-		Ksn,
-		Usd,
-		Brl,
-		Cad,
-		Gbp,
-		Eur,
-		Bdt,
-		Cop
-	},
-	ALL_CURRENCIES
-);
+impl Currency {
+	pub fn code(&self) -> &str {
+		std::str::from_utf8(&self.code).unwrap_or("???")
+	}
+
+	pub fn guild(&self) -> GuildId {
+		self.guild
+	}
+
+	fn from_code(guild: GuildId, code: &str) -> Option<Self> {
+		let code = code.to_uppercase();
+		let bytes: [u8; 3] = code.as_bytes().try_into().ok()?;
+
+		if bytes.iter().all(u8::is_ascii_uppercase) {
+			Some(Self { guild, code: bytes })
+		} else {
+			None
+		}
+	}
+}
 
+#[derive(Debug, Clone)]
 pub struct CurrencyInfo<'a> {
+	pub guild: GuildId,
 	pub code: Cow<'a, str>,
 	pub prefix: Cow<'a, str>,
 	pub posfix: Cow<'a, str>,
 	pub name: Cow<'a, str>,
 	pub picture: Cow<'a, str>,
 	pub subunitexp: i32,
+	pub retired: bool,
 }
 
-impl TryFrom<&str> for Currency {
-	type Error = anyhow::Error;
+impl CurrencyInfo<'_> {
+	/// Number of decimal places below the main unit.
+	pub fn decimals(&self) -> u32 {
+		(-self.subunitexp).max(0) as u32
+	}
+
+	/// Minor units in one main unit.
+	pub fn unit(&self) -> i64 {
+		10i64.pow(self.decimals())
+	}
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct CurrencyRow {
+	guild_id: GuildId,
+	code: String,
+	name: String,
+	prefix: String,
+	posfix: String,
+	picture: String,
+	subunitexp: i32,
+	retired: bool,
+}
+
+/// Currencies known to the bank, in creation order. Filled from the
+/// `Currency` table by `load_registry`.
+static REGISTRY: RwLock<Vec<CurrencyInfo<'static>>> = RwLock::new(Vec::new());
+
+/// Every currency registered in `guild`, retired ones included.
+pub fn all_currencies(guild: GuildId) -> Vec<Currency> {
+	REGISTRY
+		.read()
+		.unwrap()
+		.iter()
+		.filter(|info| info.guild == guild)
+		.filter_map(|info| Currency::from_code(info.guild, &info.code))
+		.collect()
+}
+
+/// Currencies of `guild` that still accept new operations.
+pub fn active_currencies(guild: GuildId) -> Vec<Currency> {
+	REGISTRY
+		.read()
+		.unwrap()
+		.iter()
+		.filter(|info| info.guild == guild && !info.retired)
+		.filter_map(|info| Currency::from_code(info.guild, &info.code))
+		.collect()
+}
+
+/// The currency with `code` in every guild that has one, templates excluded.
+pub fn currencies_with_code(code: &str) -> Vec<Currency> {
+	REGISTRY
+		.read()
+		.unwrap()
+		.iter()
+		.filter(|info| info.guild != TEMPLATE_GUILD && info.code.eq_ignore_ascii_case(code))
+		.filter_map(|info| Currency::from_code(info.guild, &info.code))
+		.collect()
+}
+
+pub async fn load_registry(pool: &SqlitePool) -> anyhow::Result<()> {
+	let rows = sqlx::query_as::<_, CurrencyRow>("SELECT * FROM Currency ORDER BY rowid")
+		.fetch_all(pool)
+		.await?;
+
+	let currencies = rows
+		.into_iter()
+		.map(|row| CurrencyInfo {
+			guild: row.guild_id,
+			code: row.code.into(),
+			prefix: row.prefix.into(),
+			posfix: row.posfix.into(),
+			name: row.name.into(),
+			picture: row.picture.into(),
+			subunitexp: row.subunitexp,
+			retired: row.retired,
+		})
+		.collect();
+
+	*REGISTRY.write().unwrap() = currencies;
+	Ok(())
+}
+
+/// Gives `guild` a copy of the template currencies, unless it already has
+/// currencies of its own.
+pub async fn seed_guild(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<()> {
+	if !all_currencies(guild).is_empty() {
+		return Ok(());
+	}
+
+	sqlx::query(
+		r#"INSERT OR IGNORE INTO Currency
+		(guild_id, code, name, prefix, posfix, picture, subunitexp, retired)
+		SELECT ?, code, name, prefix, posfix, picture, subunitexp, retired
+		FROM Currency WHERE guild_id=?
+		ORDER BY rowid"#,
+	)
+	.bind(guild)
+	.bind(TEMPLATE_GUILD)
+	.execute(pool)
+	.await?;
+
+	load_registry(pool).await
+}
+
+fn check_subunitexp(subunitexp: i32) -> anyhow::Result<()> {
+	if !SUBUNITEXP_RANGE.contains(&subunitexp) {
+		anyhow::bail!(
+			"The subunit exponent must be between {} and {}.",
+			SUBUNITEXP_RANGE.start(),
+			SUBUNITEXP_RANGE.end()
+		);
+	}
+
+	Ok(())
+}
 
-	fn try_from(value: &str) -> Result<Self, Self::Error> {
-		if value.len() != 3 {
-			anyhow::bail!("No matching currency");
+pub async fn create_currency(pool: &SqlitePool, info: &CurrencyInfo<'_>) -> anyhow::Result<()> {
+	if Currency::from_code(info.guild, &info.code).is_none() {
+		anyhow::bail!("Currency codes are made of three letters.");
+	}
+	check_subunitexp(info.subunitexp)?;
+
+	sqlx::query(
+		r#"INSERT INTO Currency
+		(guild_id, code, name, prefix, posfix, picture, subunitexp)
+		VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+	)
+	.bind(info.guild)
+	.bind(info.code.to_uppercase())
+	.bind(info.name.as_ref())
+	.bind(info.prefix.as_ref())
+	.bind(info.posfix.as_ref())
+	.bind(info.picture.as_ref())
+	.bind(info.subunitexp)
+	.execute(pool)
+	.await?;
+
+	load_registry(pool).await
+}
+
+/// Writes every field of `info` back to the registry. The subunit exponent
+/// can only change while the currency has no ledger rows, as existing minor
+/// unit amounts would otherwise be rescaled.
+pub async fn update_currency(pool: &SqlitePool, info: &CurrencyInfo<'_>) -> anyhow::Result<()> {
+	check_subunitexp(info.subunitexp)?;
+
+	let current =
+		sqlx::query_as::<_, (i32,)>("SELECT subunitexp FROM Currency WHERE guild_id=? AND code=?")
+			.bind(info.guild)
+			.bind(info.code.as_ref())
+			.fetch_optional(pool)
+			.await?;
+
+	match current {
+		None => anyhow::bail!("No matching currency"),
+		Some((subunitexp,)) if subunitexp != info.subunitexp => {
+			let (rows,) = sqlx::query_as::<_, (i64,)>(
+				"SELECT COUNT(*) FROM Transfer WHERE guild_id=? AND currency=?",
+			)
+			.bind(info.guild)
+			.bind(info.code.as_ref())
+			.fetch_one(pool)
+			.await?;
+
+			if rows > 0 {
+				anyhow::bail!(
+					"The subunit exponent cannot change once the currency has operations."
+				);
+			}
 		}
+		Some(_) => {}
+	}
+
+	sqlx::query(
+		r#"UPDATE Currency
+		SET name=?, prefix=?, posfix=?, picture=?, subunitexp=?, retired=?
+		WHERE guild_id=? AND code=?"#,
+	)
+	.bind(info.name.as_ref())
+	.bind(info.prefix.as_ref())
+	.bind(info.posfix.as_ref())
+	.bind(info.picture.as_ref())
+	.bind(info.subunitexp)
+	.bind(info.retired)
+	.bind(info.guild)
+	.bind(info.code.as_ref())
+	.execute(pool)
+	.await?;
 
-		match &value.to_uppercase()[0..3] {
-			"KSN" => Ok(Self::Ksn),
-			"USD" => Ok(Self::Usd),
-			"BRL" => Ok(Self::Brl),
-			"CAD" => Ok(Self::Cad),
-			"GBP" => Ok(Self::Gbp),
-			"EUR" => Ok(Self::Eur),
-			"BDT" => Ok(Self::Bdt),
-			"COP" => Ok(Self::Cop),
-			_ => anyhow::bail!("No matching currency"),
+	load_registry(pool).await
+}
+
+/// Whether `currency` is retired, read from the database so it can be checked
+/// inside the transaction that would move it.
+pub async fn is_retired(conn: &mut SqliteConnection, currency: Currency) -> anyhow::Result<bool> {
+	Ok(
+		sqlx::query_as::<_, (bool,)>("SELECT retired FROM Currency WHERE guild_id=? AND code=?")
+			.bind(currency.guild())
+			.bind(currency.code())
+			.fetch_optional(conn)
+			.await?
+			.is_none_or(|row| row.0),
+	)
+}
+
+/// Resolves a currency code against the registry of a guild.
+impl TryFrom<(GuildId, &str)> for Currency {
+	type Error = anyhow::Error;
+
+	fn try_from((guild, value): (GuildId, &str)) -> Result<Self, Self::Error> {
+		let currency = match Self::from_code(guild, value) {
+			Some(currency) => currency,
+			None => anyhow::bail!("No matching currency"),
+		};
+
+		let registered = REGISTRY
+			.read()
+			.unwrap()
+			.iter()
+			.any(|info| info.guild == guild && info.code == currency.code());
+
+		if registered {
+			Ok(currency)
+		} else {
+			anyhow::bail!("No matching currency")
 		}
 	}
 }
 
 impl<'a> From<Currency> for CurrencyInfo<'a> {
 	fn from(currency: Currency) -> Self {
-		match currency {
-			Currency::Ksn => CurrencyInfo {
-				code: "KSN".into(),
-				prefix: "K$".into(),
-				posfix: "nepers".into(),
-				name: "Kid Server Neper".into(), 
-				picture: "https://media.discordapp.net/attachments/1153482364907962509/1153858790341492766/twitchiconpng1-tanglesheep.png".into(),
-				subunitexp: -2
-			},
-			Currency::Usd => CurrencyInfo {
-				code: "USD".into(),
-				prefix: "U$".into(),
-				posfix: "dollars".into(),
-				name: "American Dollar".into(),
-				picture : "https://media.discordapp.net/attachments/1153482364907962509/1153858888895041599/555526.png".into(),
-				subunitexp: -2
-			},
-			Currency::Brl => CurrencyInfo {
-				code: "BRL".into(),
-				prefix: "R$".into(),
-				posfix: "reals".into(),
-				name: "Brazilian Real".into(),
-				picture : "https://media.discordapp.net/attachments/1153482364907962509/1153858933551808605/206597.png".into(),
-				subunitexp: -2
-			},
-			Currency::Cad => CurrencyInfo {
-				code: "CAD".into(),
-				prefix: "C$".into(),
-				posfix: "dollars".into(),
-				name: "Canadian Dollar".into(),
-				picture : "https://cdn.discordapp.com/attachments/1153482364907962509/1153861273793081414/555473.png".into(),
-				subunitexp: -2
-			},
-			Currency::Gbp => CurrencyInfo {
-				code: "GBP".into(),
-				prefix: "£".into(),
-				posfix: "pounds".into(),
-				name: "British Pound".into(),
-				picture : "https://media.discordapp.net/attachments/1153482364907962509/1153861461182001212/555417.png".into(),
-				subunitexp: -2
-			},
-			Currency::Eur => CurrencyInfo {
-				code: "EUR".into(),
-				prefix: "€".into(),
-				posfix: "euros".into(),
-				name: "European Euro".into(),
-				picture : "https://media.discordapp.net/attachments/1153482364907962509/1153861704141262858/330426.png".into(),
-				subunitexp: -2
-			},
-			Currency::Bdt => CurrencyInfo {
-				code: "BDT".into(),
-				prefix: "৳".into(),
-				posfix: "takas".into(),
-				name: "Bangladeshi Taka".into(),
-				picture : "https://cdn.discordapp.com/attachments/1153482364907962509/1153862592121548800/5327225.png".into(),
-				subunitexp: -2
-			},
-			Currency::Cop => CurrencyInfo {
-				code: "COP".into(),
-				prefix: "$".into(),
-				posfix: "pesos".into(),
-				name: "Colombian Peso".into(),
-				picture : "https://media.discordapp.net/attachments/1153482364907962509/1154949125960372235/330508.png".into(),
-				subunitexp: -2
+		let registry = REGISTRY.read().unwrap();
+
+		match registry
+			.iter()
+			.find(|info| info.guild == currency.guild && info.code == currency.code())
+		{
+			Some(info) => info.clone(),
+			None => CurrencyInfo {
+				guild: currency.guild,
+				code: currency.code().to_owned().into(),
+				prefix: currency.code().to_owned().into(),
+				posfix: currency.code().to_owned().into(),
+				name: currency.code().to_owned().into(),
+				picture: "".into(),
+				subunitexp: 0,
+				retired: true,
 			},
 		}
 	}
 }
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum AmountError {
+	Invalid,
+	TooPrecise { code: String, decimals: u32 },
+	TooLarge,
+}
+
+impl std::fmt::Display for AmountError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			AmountError::Invalid => write!(
+				f,
+				"The value is not a valid amount. Use a form like `1,250.50`, `2.5k` or `1m`."
+			),
+			AmountError::TooPrecise { code, decimals } => write!(
+				f,
+				"{} amounts allow at most {} decimal places.",
+				code, decimals
+			),
+			AmountError::TooLarge => write!(f, "The value is too large."),
+		}
+	}
+}
+
+impl std::error::Error for AmountError {}
+
+/// Tells whether `word` has the shape of an amount, so it can be picked out of
+/// a command before the currency is known.
+pub fn is_amount(word: &str) -> bool {
+	let word = word.strip_prefix('-').unwrap_or(word);
+	let word = word
+		.strip_suffix(|c: char| c.eq_ignore_ascii_case(&'k') || c.eq_ignore_ascii_case(&'m'))
+		.unwrap_or(word);
+
+	word.chars().any(|c| c.is_ascii_digit())
+		&& word
+			.chars()
+			.all(|c| c.is_ascii_digit() || c == ',' || c == '.')
+}
+
+/// Converts a decimal string straight into integer minor units of `currency`.
+///
+/// Accepts an optional sign, `,` thousands separators and a `k` or `m`
+/// suffix. Amounts finer than the currency's smallest unit are rejected.
+pub fn parse_amount(word: &str, currency: Currency) -> Result<i64, AmountError> {
+	let info = CurrencyInfo::from(currency);
+
+	let (negative, word) = match word.strip_prefix('-') {
+		Some(rest) => (true, rest),
+		None => (false, word),
+	};
+
+	let (word, suffix_exp) = match word.chars().last() {
+		Some('k') | Some('K') => (&word[..word.len() - 1], 3),
+		Some('m') | Some('M') => (&word[..word.len() - 1], 6),
+		_ => (word, 0),
+	};
+
+	let (integer_part, fraction_part) = match word.split_once('.') {
+		Some((integer_part, fraction_part)) => (integer_part, fraction_part),
+		None => (word, ""),
+	};
+
+	// Thousands separators must split the integer part in groups of three.
+	let groups: Vec<&str> = integer_part.split(',').collect();
+	let valid_groups = groups.iter().enumerate().all(|(index, group)| {
+		let valid_length = if index == 0 {
+			!group.is_empty() && (groups.len() == 1 || group.len() <= 3)
+		} else {
+			group.len() == 3
+		};
+
+		valid_length && group.chars().all(|c| c.is_ascii_digit())
+	});
+
+	let bare_fraction = integer_part.is_empty() && !fraction_part.is_empty();
+	if !valid_groups && !bare_fraction {
+		return Err(AmountError::Invalid);
+	}
+
+	if !fraction_part.chars().all(|c| c.is_ascii_digit()) {
+		return Err(AmountError::Invalid);
+	}
+
+	let decimals = info.decimals();
+	let scale = decimals + suffix_exp;
+	let fraction_part = fraction_part.trim_end_matches('0');
+	if fraction_part.len() as u32 > scale {
+		return Err(AmountError::TooPrecise {
+			code: info.code.into_owned(),
+			decimals,
+		});
+	}
+
+	let digits = format!(
+		"{}{}{}",
+		groups.concat(),
+		fraction_part,
+		"0".repeat((scale - fraction_part.len() as u32) as usize)
+	);
+
+	let value = digits.parse::<i64>().map_err(|_| AmountError::TooLarge)?;
+	Ok(if negative { -value } else { value })
+}
+
+/// Largest magnitude of minor units an `f64` holds exactly.
+const MAX_EXACT_NUMBER: f64 = (1u64 << f64::MANTISSA_DIGITS) as f64;
+
+/// Converts a number sent by Discord into integer minor units of `currency`.
+///
+/// The number is accepted only when it is the closest `f64` to a whole number
+/// of minor units, which is what Discord sends for an amount typed with no more
+/// decimals than the currency has. Anything finer is rejected as too precise.
+pub fn amount_from_number(value: f64, currency: Currency) -> Result<i64, AmountError> {
+	let info = CurrencyInfo::from(currency);
+
+	if !value.is_finite() {
+		return Err(AmountError::Invalid);
+	}
+
+	let unit = info.unit() as f64;
+	let minor = (value * unit).round();
+	if minor.abs() > MAX_EXACT_NUMBER {
+		return Err(AmountError::TooLarge);
+	}
+
+	// Both are exact, so the division rounds to the closest `f64` of the amount.
+	if minor / unit != value {
+		let decimals = info.decimals();
+		return Err(AmountError::TooPrecise {
+			code: info.code.into_owned(),
+			decimals,
+		});
+	}
+
+	Ok(minor as i64)
+}
+
+/// How a `Money` value names its currency when formatted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoneyStyle {
+	/// `K$ 1,234.56`
+	Symbol,
+	/// `KSN 1,234.56`
+	Code,
+	/// `1,234.56 nepers`
+	Name,
+}
+
+/// An exact amount of money, held in minor units of its currency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Money {
+	pub amount: i64,
+	pub currency: Currency,
+}
+
+impl Money {
+	pub fn new(amount: i64, currency: Currency) -> Self {
+		Self { amount, currency }
+	}
+
+	/// Formats the amount with `,` grouping and exactly as many decimal places
+	/// as the currency's subunit exponent asks for.
+	pub fn format_amount(&self) -> String {
+		let info = CurrencyInfo::from(self.currency);
+		let decimals = info.decimals() as usize;
+
+		let digits = self.amount.unsigned_abs().to_string();
+		let digits = format!("{:0>width$}", digits, width = decimals + 1);
+		let (integer_part, fraction_part) = digits.split_at(digits.len() - decimals);
+
+		let mut result = String::new();
+		if self.amount < 0 {
+			result.push('-');
+		}
+
+		for (index, digit) in integer_part.chars().enumerate() {
+			if index > 0 && (integer_part.len() - index).is_multiple_of(3) {
+				result.push(',');
+			}
+			result.push(digit);
+		}
+
+		if decimals > 0 {
+			result.push('.');
+			result.push_str(fraction_part);
+		}
+
+		result
+	}
+
+	pub fn format(&self, style: MoneyStyle) -> String {
+		let info = CurrencyInfo::from(self.currency);
+
+		match style {
+			MoneyStyle::Symbol => format!("{} {}", info.prefix, self.format_amount()),
+			MoneyStyle::Code => format!("{} {}", info.code, self.format_amount()),
+			MoneyStyle::Name => format!("{} {}", self.format_amount(), info.posfix),
+		}
+	}
+}
+
+impl std::fmt::Display for Money {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}", self.format(MoneyStyle::Code))
+	}
+}
+
+/// Adds a currency to the registry without a database. Each test uses a guild
+/// of its own, since the registry is shared by the tests running in parallel.
+#[cfg(test)]
+pub fn register_test_currency(guild: GuildId, code: &str, subunitexp: i32) -> Currency {
+	REGISTRY.write().unwrap().push(CurrencyInfo {
+		guild,
+		code: code.to_owned().into(),
+		prefix: "$".into(),
+		posfix: "dollars".into(),
+		name: "Dollar".into(),
+		picture: "".into(),
+		subunitexp,
+		retired: false,
+	});
+
+	Currency::try_from((guild, code)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_amount_is_exact() {
+		let usd = register_test_currency(801, "USD", -2);
+
+		assert_eq!(parse_amount("0.29", usd), Ok(29));
+		assert_eq!(parse_amount("0.1", usd), Ok(10));
+		assert_eq!(parse_amount("12", usd), Ok(1200));
+		assert_eq!(parse_amount("-3.50", usd), Ok(-350));
+		assert_eq!(parse_amount(".5", usd), Ok(50));
+	}
+
+	#[test]
+	fn parse_amount_reads_separators_and_suffixes() {
+		let usd = register_test_currency(802, "USD", -2);
+
+		assert_eq!(parse_amount("1,234.56", usd), Ok(123456));
+		assert_eq!(parse_amount("1,234,567", usd), Ok(123456700));
+		assert_eq!(parse_amount("1.5k", usd), Ok(150000));
+		assert_eq!(parse_amount("2K", usd), Ok(200000));
+		assert_eq!(parse_amount("1m", usd), Ok(100000000));
+		assert_eq!(parse_amount("0.00001m", usd), Ok(1000));
+
+		assert_eq!(parse_amount("1,23", usd), Err(AmountError::Invalid));
+		assert_eq!(parse_amount("12,34.5", usd), Err(AmountError::Invalid));
+		assert_eq!(parse_amount(",123", usd), Err(AmountError::Invalid));
+	}
+
+	#[test]
+	fn parse_amount_rejects_excess_decimals() {
+		let usd = register_test_currency(803, "USD", -2);
+		let whole = register_test_currency(803, "JPY", 0);
+
+		let too_precise = || AmountError::TooPrecise {
+			code: String::from("USD"),
+			decimals: 2,
+		};
+		assert_eq!(parse_amount("0.291", usd), Err(too_precise()));
+		assert_eq!(parse_amount("1.5000", usd), Ok(150));
+		assert_eq!(parse_amount("0.000001k", usd), Err(too_precise()));
+		assert_eq!(
+			parse_amount("1.5", whole),
+			Err(AmountError::TooPrecise {
+				code: String::from("JPY"),
+				decimals: 0,
+			})
+		);
+	}
+
+	#[test]
+	fn parse_amount_rejects_non_decimal_forms() {
+		let usd = register_test_currency(804, "USD", -2);
+
+		for word in [
+			"inf", "-inf", "NaN", "1e3", "0x10", "", "-", ".", "k", "1.2.3", "+5", "1 000",
+		] {
+			assert_eq!(
+				parse_amount(word, usd),
+				Err(AmountError::Invalid),
+				"{}",
+				word
+			);
+		}
+	}
+
+	#[test]
+	fn parse_amount_rejects_overflow() {
+		let usd = register_test_currency(805, "USD", -2);
+
+		assert_eq!(parse_amount("92233720368547758.07", usd), Ok(i64::MAX));
+		assert_eq!(
+			parse_amount("92233720368547758.08", usd),
+			Err(AmountError::TooLarge)
+		);
+		assert_eq!(
+			parse_amount("9223372036854775807", usd),
+			Err(AmountError::TooLarge)
+		);
+		assert_eq!(
+			parse_amount("100000000000000m", usd),
+			Err(AmountError::TooLarge)
+		);
+	}
+
+	#[test]
+	fn amount_from_number_is_exact() {
+		let usd = register_test_currency(806, "USD", -2);
+		let yen = register_test_currency(806, "JPY", 0);
+
+		assert_eq!(amount_from_number(0.29, usd), Ok(29));
+		assert!(amount_from_number(0.1 + 0.2, usd).is_err());
+		assert_eq!(amount_from_number(1234.56, usd), Ok(123456));
+		assert_eq!(amount_from_number(-3.5, usd), Ok(-350));
+		assert_eq!(amount_from_number(12.0, yen), Ok(12));
+		assert_eq!(
+			amount_from_number(0.001, usd),
+			Err(AmountError::TooPrecise {
+				code: String::from("USD"),
+				decimals: 2
+			})
+		);
+		assert!(amount_from_number(1.5, yen).is_err());
+	}
+
+	#[test]
+	fn amount_from_number_rejects_special_and_huge_values() {
+		let usd = register_test_currency(807, "USD", -2);
+
+		assert_eq!(amount_from_number(f64::NAN, usd), Err(AmountError::Invalid));
+		assert_eq!(
+			amount_from_number(f64::INFINITY, usd),
+			Err(AmountError::Invalid)
+		);
+		assert_eq!(amount_from_number(1e300, usd), Err(AmountError::TooLarge));
+		assert_eq!(amount_from_number(9e13, usd), Ok(9_000_000_000_000_000));
+	}
+}
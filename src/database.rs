@@ -0,0 +1,314 @@
+use anyhow::Context as _;
+use futures::future::BoxFuture;
+use serenity::prelude::*;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::{Executor, SqliteConnection};
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::chain;
+use crate::operation::get_current_time;
+
+pub struct Database;
+
+impl TypeMapKey for Database {
+	type Value = SqlitePool;
+}
+
+pub struct DatabaseConfig {
+	pub url: String,
+	pub wal: bool,
+	pub busy_timeout: Duration,
+	pub max_connections: u32,
+	/// Guild that owns the ledger written before guilds were supported.
+	pub default_guild: Option<i64>,
+}
+
+impl DatabaseConfig {
+	/// Reads `BANK_DATABASE_URL`, `BANK_DATABASE_WAL`, `BANK_DATABASE_BUSY_TIMEOUT`
+	/// (milliseconds), `BANK_DATABASE_MAX_CONNECTIONS` and `BANK_DEFAULT_GUILD`,
+	/// falling back to the historical `bank_database.db` in the working directory.
+	pub fn from_env() -> anyhow::Result<Self> {
+		let url = env::var("BANK_DATABASE_URL")
+			.unwrap_or_else(|_| String::from("sqlite://bank_database.db"));
+
+		let wal = match env::var("BANK_DATABASE_WAL") {
+			Ok(value) => matches!(value.to_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+			Err(_) => true,
+		};
+
+		let busy_timeout = match env::var("BANK_DATABASE_BUSY_TIMEOUT") {
+			Ok(value) => Duration::from_millis(value.parse()?),
+			Err(_) => Duration::from_secs(5),
+		};
+
+		let max_connections = match env::var("BANK_DATABASE_MAX_CONNECTIONS") {
+			Ok(value) => value.parse()?,
+			Err(_) => 8,
+		};
+
+		let default_guild = match env::var("BANK_DEFAULT_GUILD") {
+			Ok(value) => Some(value.parse()?),
+			Err(_) => None,
+		};
+
+		Ok(Self {
+			url,
+			wal,
+			busy_timeout,
+			max_connections,
+			default_guild,
+		})
+	}
+
+	pub async fn connect(&self) -> anyhow::Result<SqlitePool> {
+		let journal_mode = if self.wal {
+			SqliteJournalMode::Wal
+		} else {
+			SqliteJournalMode::Delete
+		};
+
+		let options = SqliteConnectOptions::from_str(&self.url)?
+			.create_if_missing(true)
+			.journal_mode(journal_mode)
+			.busy_timeout(self.busy_timeout);
+
+		Ok(SqlitePoolOptions::new()
+			.max_connections(self.max_connections)
+			.connect_with(options)
+			.await?)
+	}
+
+	/// Opens the database without changing it in any way, for the offline audit.
+	pub async fn connect_read_only(&self) -> anyhow::Result<SqlitePool> {
+		let options = SqliteConnectOptions::from_str(&self.url)?
+			.read_only(true)
+			.busy_timeout(self.busy_timeout);
+
+		Ok(SqlitePoolOptions::new()
+			.max_connections(1)
+			.connect_with(options)
+			.await?)
+	}
+}
+
+pub async fn get_pool(ctx: &Context) -> SqlitePool {
+	ctx.data
+		.read()
+		.await
+		.get::<Database>()
+		.expect("Expected a database pool in the TypeMap.")
+		.clone()
+}
+
+/// Runs `body` inside a `BEGIN IMMEDIATE` transaction, so the write lock is
+/// taken before any balance is read and concurrent transfers are serialized.
+pub async fn immediate_transaction<T, F>(conn: &mut SqliteConnection, body: F) -> anyhow::Result<T>
+where
+	F: for<'c> FnOnce(&'c mut SqliteConnection) -> BoxFuture<'c, anyhow::Result<T>>,
+{
+	conn.execute("BEGIN IMMEDIATE").await?;
+
+	match body(conn).await {
+		Ok(value) => match conn.execute("COMMIT").await {
+			Ok(_) => Ok(value),
+			Err(err) => {
+				// A failed COMMIT (such as SQLITE_BUSY) leaves the transaction open,
+				// and the connection goes back to the pool.
+				conn.execute("ROLLBACK").await.ok();
+				Err(err.into())
+			}
+		},
+		Err(err) => {
+			conn.execute("ROLLBACK").await.ok();
+			Err(err)
+		}
+	}
+}
+
+/// Schema migrations embedded in the binary, in the order they must be applied.
+/// The position in this list (starting at 1) is the schema version.
+const MIGRATIONS: &[&str] = &[
+	include_str!("../migrations/0001_initial.sql"),
+	include_str!("../migrations/0002_user_settings.sql"),
+	include_str!("../migrations/0003_currencies.sql"),
+	include_str!("../migrations/0004_accounts.sql"),
+	include_str!("../migrations/0005_transfer_hash_chain.sql"),
+	include_str!("../migrations/0006_transfer_reversal.sql"),
+	include_str!("../migrations/0007_pending_transfers.sql"),
+	include_str!("../migrations/0008_invoices.sql"),
+	include_str!("../migrations/0009_exchange_rates.sql"),
+	include_str!("../migrations/0010_rate_history.sql"),
+	include_str!("../migrations/0011_guilds.sql"),
+	include_str!("../migrations/0012_roles.sql"),
+	include_str!("../migrations/0013_admin_log.sql"),
+	include_str!("../migrations/0014_guild_settings.sql"),
+	include_str!("../migrations/0015_hash_guild.sql"),
+];
+
+pub const SCHEMA_VERSION: i64 = MIGRATIONS.len() as i64;
+
+/// The migration that hash chains the ledger.
+const CHAIN_VERSION: i64 = 5;
+
+/// Brings the database up to `SCHEMA_VERSION`, applying each pending migration
+/// in its own transaction and recording it in `SchemaVersion`.
+///
+/// `default_guild` is exposed to the migrations as `temp.MigrationSetting`, so
+/// the one introducing guilds can assign the existing ledger to it.
+pub async fn migrate(pool: &SqlitePool, default_guild: Option<i64>) -> anyhow::Result<()> {
+	let mut conn = pool.acquire().await?;
+
+	conn.execute(
+		r#"CREATE TEMP TABLE IF NOT EXISTS MigrationSetting (
+		key VARCHAR(64) PRIMARY KEY,
+		value INT
+		)"#,
+	)
+	.await?;
+	sqlx::query(
+		"INSERT OR REPLACE INTO temp.MigrationSetting (key, value) VALUES ('default_guild', ?)",
+	)
+	.bind(default_guild)
+	.execute(&mut *conn)
+	.await?;
+
+	conn.execute(
+		r#"CREATE TABLE IF NOT EXISTS SchemaVersion (
+		version INTEGER PRIMARY KEY,
+		applied_date INT NOT NULL
+		)"#,
+	)
+	.await?;
+
+	let current_version = get_schema_version(&mut conn).await?;
+	if current_version > SCHEMA_VERSION {
+		anyhow::bail!(
+			"Database schema version {} is newer than this binary supports ({}).",
+			current_version,
+			SCHEMA_VERSION
+		);
+	}
+
+	// Migration 11 hands the existing ledger to the default guild. A database
+	// created from the old schema.sql is at version 0 but may hold rows.
+	if current_version < 11 && default_guild.is_none() {
+		for table in GUILD_SCOPED_TABLES {
+			if has_rows(&mut conn, table).await? {
+				anyhow::bail!(
+					"Set BANK_DEFAULT_GUILD to the guild that owns the existing ledger ({} has rows).",
+					table
+				);
+			}
+		}
+	}
+
+	// Rows written before the chain existed are sealed along with the last
+	// migration, when the code hashing them matches the schema. This happens
+	// only once, so rows whose hashes are removed later stay unsealed.
+	let seal_legacy_rows = current_version < CHAIN_VERSION;
+
+	for (index, migration) in MIGRATIONS.iter().enumerate() {
+		let version = index as i64 + 1;
+		if version <= current_version {
+			continue;
+		}
+
+		immediate_transaction(&mut conn, |conn| {
+			Box::pin(async move {
+				conn.execute(*migration).await?;
+				sqlx::query("INSERT INTO SchemaVersion (version, applied_date) VALUES (?, ?)")
+					.bind(version)
+					.bind(get_current_time())
+					.execute(&mut *conn)
+					.await?;
+
+				if seal_legacy_rows && version == SCHEMA_VERSION {
+					chain::seal_unhashed_rows(conn).await?;
+				}
+
+				Ok(())
+			})
+		})
+		.await
+		.with_context(|| format!("Could not apply database migration {}.", version))?;
+
+		println!("Applied database migration {}.", version);
+	}
+
+	Ok(())
+}
+
+/// Tables whose rows migration 11 assigns to the default guild.
+const GUILD_SCOPED_TABLES: &[&str] = &[
+	"Transfer",
+	"Account",
+	"PendingTransfer",
+	"Invoice",
+	"Exchange",
+	"ExchangeRate",
+	"ExchangeRateHistory",
+];
+
+/// Whether `table` exists and has rows. Tables from later migrations are
+/// missing in older databases.
+async fn has_rows(conn: &mut SqliteConnection, table: &str) -> anyhow::Result<bool> {
+	let (tables,) = sqlx::query_as::<_, (i64,)>(
+		"SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name=?",
+	)
+	.bind(table)
+	.fetch_one(&mut *conn)
+	.await?;
+	if tables == 0 {
+		return Ok(false);
+	}
+
+	let query = format!("SELECT EXISTS (SELECT 1 FROM {})", table);
+	let (rows,) = sqlx::query_as::<_, (bool,)>(&query)
+		.fetch_one(&mut *conn)
+		.await?;
+	Ok(rows)
+}
+
+/// Schema version of a database that is not migrated, such as one being
+/// audited. Zero when it never was.
+pub async fn read_schema_version(pool: &SqlitePool) -> anyhow::Result<i64> {
+	let (tables,) = sqlx::query_as::<_, (i64,)>(
+		"SELECT COUNT(*) FROM sqlite_master WHERE type='table' AND name='SchemaVersion'",
+	)
+	.fetch_one(pool)
+	.await?;
+	if tables == 0 {
+		return Ok(0);
+	}
+
+	let mut conn = pool.acquire().await?;
+	get_schema_version(&mut conn).await
+}
+
+async fn get_schema_version(conn: &mut SqliteConnection) -> anyhow::Result<i64> {
+	Ok(
+		sqlx::query_as::<_, (Option<i64>,)>("SELECT MAX(version) FROM SchemaVersion")
+			.fetch_one(conn)
+			.await?
+			.0
+			.unwrap_or(0),
+	)
+}
+
+/// An empty, fully migrated database in memory.
+#[cfg(test)]
+pub async fn test_pool() -> SqlitePool {
+	let config = DatabaseConfig {
+		url: String::from("sqlite::memory:"),
+		wal: false,
+		busy_timeout: Duration::from_secs(1),
+		max_connections: 1,
+		default_guild: None,
+	};
+
+	let pool = config.connect().await.unwrap();
+	migrate(&pool, None).await.unwrap();
+	pool
+}
@@ -0,0 +1,800 @@
+use serenity::async_trait;
+use serenity::prelude::*;
+use sqlx::{SqliteConnection, SqlitePool};
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::currency::{self, Currency, CurrencyInfo};
+use crate::database::immediate_transaction;
+use crate::operation::{get_current_time, try_transfer, GuildId, TransferStatus, UserId, BANK_ID};
+
+/// Rates are fixed point with this many units per 1.0.
+pub const RATE_SCALE: i64 = 1_000_000_000;
+const RATE_DECIMALS: usize = 9;
+
+/// Spreads are given in basis points, hundredths of a percent.
+const BASIS_POINTS: i64 = 10_000;
+
+/// Parses a positive decimal rate such as `1.08` into `RATE_SCALE` units.
+pub fn parse_rate(word: &str) -> Option<i64> {
+	let (integer_part, fraction_part) = word.split_once('.').unwrap_or((word, ""));
+	if integer_part.is_empty() && fraction_part.is_empty() {
+		return None;
+	}
+
+	let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+	if !all_digits(integer_part) || !all_digits(fraction_part) {
+		return None;
+	}
+
+	let fraction_part = fraction_part.trim_end_matches('0');
+	if fraction_part.len() > RATE_DECIMALS {
+		return None;
+	}
+
+	let digits = format!(
+		"{}{}{}",
+		integer_part,
+		fraction_part,
+		"0".repeat(RATE_DECIMALS - fraction_part.len())
+	);
+
+	digits.parse::<i64>().ok().filter(|rate| *rate > 0)
+}
+
+/// Formats a `RATE_SCALE` rate without trailing zeros.
+pub fn format_rate(rate: i64) -> String {
+	let integer_part = rate / RATE_SCALE;
+	let fraction_part = format!("{:09}", rate % RATE_SCALE);
+	let fraction_part = fraction_part.trim_end_matches('0');
+
+	if fraction_part.is_empty() {
+		integer_part.to_string()
+	} else {
+		format!("{}.{}", integer_part, fraction_part)
+	}
+}
+
+/// A source of exchange rates. Each rate is the value of one unit of the
+/// currency in a reference unit shared by all currencies of the source, so
+/// any two of them can be crossed.
+#[async_trait]
+pub trait RateProvider: Send + Sync {
+	fn name(&self) -> String;
+
+	async fn fetch_rates(&self) -> anyhow::Result<Vec<(String, i64)>>;
+}
+
+/// Reads rates from a text file with one `CODE RATE` pair per line. Blank
+/// lines and lines starting with `#` are ignored.
+pub struct FileRateProvider {
+	pub path: PathBuf,
+}
+
+#[async_trait]
+impl RateProvider for FileRateProvider {
+	fn name(&self) -> String {
+		format!("file:{}", self.path.display())
+	}
+
+	async fn fetch_rates(&self) -> anyhow::Result<Vec<(String, i64)>> {
+		parse_rate_file(&tokio::fs::read_to_string(&self.path).await?)
+	}
+}
+
+/// Parses the contents of a `FileRateProvider` file.
+pub fn parse_rate_file(contents: &str) -> anyhow::Result<Vec<(String, i64)>> {
+	let mut rates = vec![];
+
+	for (index, line) in contents.lines().enumerate() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		match line.split_whitespace().collect::<Vec<_>>()[..] {
+			[code, rate] => match parse_rate(rate) {
+				Some(rate) => rates.push((code.to_uppercase(), rate)),
+				None => anyhow::bail!("Line {}: invalid rate `{}`.", index + 1, rate),
+			},
+			_ => anyhow::bail!("Line {}: expected `CODE RATE`.", index + 1),
+		}
+	}
+
+	Ok(rates)
+}
+
+pub struct RateSource;
+
+impl TypeMapKey for RateSource {
+	type Value = Arc<dyn RateProvider>;
+}
+
+pub struct ExchangeSpread;
+
+impl TypeMapKey for ExchangeSpread {
+	type Value = i64;
+}
+
+/// Reads `BANK_RATES_FILE`, the file rates are fed from, and
+/// `BANK_RATES_REFRESH`, how often it is read again in seconds.
+pub fn provider_from_env() -> anyhow::Result<Option<(Arc<dyn RateProvider>, Duration)>> {
+	let path = match env::var("BANK_RATES_FILE") {
+		Ok(path) => PathBuf::from(path),
+		Err(_) => return Ok(None),
+	};
+
+	let refresh = match env::var("BANK_RATES_REFRESH") {
+		Ok(value) => Duration::from_secs(value.parse()?),
+		Err(_) => Duration::from_secs(60 * 60),
+	};
+
+	Ok(Some((Arc::new(FileRateProvider { path }), refresh)))
+}
+
+/// Reads `BANK_EXCHANGE_SPREAD`, the bank's cut on exchanges in basis points.
+pub fn spread_from_env() -> anyhow::Result<i64> {
+	let spread = match env::var("BANK_EXCHANGE_SPREAD") {
+		Ok(value) => value.parse()?,
+		Err(_) => 0,
+	};
+
+	if !(0..BASIS_POINTS).contains(&spread) {
+		anyhow::bail!(
+			"The exchange spread must be between 0 and {} basis points.",
+			BASIS_POINTS - 1
+		);
+	}
+
+	Ok(spread)
+}
+
+pub async fn get_rate_provider(ctx: &Context) -> Option<Arc<dyn RateProvider>> {
+	ctx.data.read().await.get::<RateSource>().cloned()
+}
+
+pub async fn get_spread(ctx: &Context) -> i64 {
+	ctx.data
+		.read()
+		.await
+		.get::<ExchangeSpread>()
+		.copied()
+		.unwrap_or(0)
+}
+
+/// All rates stored for `guild` as `(code, rate, source)`, by code.
+pub async fn get_rates(
+	pool: &SqlitePool,
+	guild: GuildId,
+) -> anyhow::Result<Vec<(String, i64, String)>> {
+	Ok(sqlx::query_as::<_, (String, i64, String)>(
+		"SELECT currency, rate, source FROM ExchangeRate WHERE guild_id=? ORDER BY currency",
+	)
+	.bind(guild)
+	.fetch_all(pool)
+	.await?)
+}
+
+async fn fetch_rate(
+	conn: &mut SqliteConnection,
+	currency: Currency,
+) -> anyhow::Result<Option<i64>> {
+	Ok(
+		sqlx::query_as::<_, (i64,)>(
+			"SELECT rate FROM ExchangeRate WHERE guild_id=? AND currency=?",
+		)
+		.bind(currency.guild())
+		.bind(currency.code())
+		.fetch_optional(conn)
+		.await?
+		.map(|row| row.0),
+	)
+}
+
+/// Source of the rates set with `!rate <code> <rate>`. Providers never
+/// overwrite them.
+pub const DIRECTOR_SOURCE: &str = "director";
+
+/// Sets the rate of `currency`, adding it to the history when it changed.
+pub async fn set_rate(
+	pool: &SqlitePool,
+	currency: Currency,
+	rate: i64,
+	source: &str,
+) -> anyhow::Result<()> {
+	store_rate(pool, currency, rate, source, true).await?;
+	Ok(())
+}
+
+/// Stores the rate of `currency` like `set_rate`, unless `overwrite_director`
+/// is false and a director set the current one. Returns whether it was stored.
+async fn store_rate(
+	pool: &SqlitePool,
+	currency: Currency,
+	rate: i64,
+	source: &str,
+	overwrite_director: bool,
+) -> anyhow::Result<bool> {
+	let mut conn = pool.acquire().await?;
+	let source = source.to_owned();
+
+	immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			if !overwrite_director {
+				let current = sqlx::query_as::<_, (String,)>(
+					"SELECT source FROM ExchangeRate WHERE guild_id=? AND currency=?",
+				)
+				.bind(currency.guild())
+				.bind(currency.code())
+				.fetch_optional(&mut *conn)
+				.await?;
+				if matches!(current, Some((current,)) if current == DIRECTOR_SOURCE) {
+					return Ok(false);
+				}
+			}
+
+			let previous = fetch_rate(conn, currency).await?;
+			let date = get_current_time();
+
+			sqlx::query(
+				r#"INSERT INTO ExchangeRate (guild_id, currency, rate, source, updated_date)
+				VALUES (?, ?, ?, ?, ?)
+				ON CONFLICT(guild_id, currency) DO UPDATE SET
+				rate=excluded.rate, source=excluded.source, updated_date=excluded.updated_date"#,
+			)
+			.bind(currency.guild())
+			.bind(currency.code())
+			.bind(rate)
+			.bind(&source)
+			.bind(date)
+			.execute(&mut *conn)
+			.await?;
+
+			if previous != Some(rate) {
+				sqlx::query(
+					r#"INSERT INTO ExchangeRateHistory (guild_id, currency, rate, source, recorded_date)
+					VALUES (?, ?, ?, ?, ?)"#,
+				)
+				.bind(currency.guild())
+				.bind(currency.code())
+				.bind(rate)
+				.bind(&source)
+				.bind(date)
+				.execute(conn)
+				.await?;
+			}
+
+			Ok(true)
+		})
+	})
+	.await
+}
+
+/// The rate `currency` had at `date`, from the history.
+pub async fn get_rate_at(
+	pool: &SqlitePool,
+	currency: Currency,
+	date: i64,
+) -> anyhow::Result<Option<i64>> {
+	Ok(sqlx::query_as::<_, (i64,)>(
+		r#"SELECT rate FROM ExchangeRateHistory
+		WHERE guild_id=? AND currency=? AND recorded_date <= ?
+		ORDER BY recorded_date DESC, id DESC
+		LIMIT 1"#,
+	)
+	.bind(currency.guild())
+	.bind(currency.code())
+	.bind(date)
+	.fetch_optional(pool)
+	.await?
+	.map(|row| row.0))
+}
+
+/// Main units of `to` per main unit of `from` now, from the rates in effect.
+/// `None` when either currency cannot be exchanged.
+pub async fn get_cross_rate(
+	pool: &SqlitePool,
+	from: Currency,
+	to: Currency,
+) -> anyhow::Result<Option<i64>> {
+	let mut conn = pool.acquire().await?;
+	Ok(
+		match (
+			fetch_rate(&mut conn, from).await?,
+			fetch_rate(&mut conn, to).await?,
+		) {
+			(Some(from_rate), Some(to_rate)) => Some(cross_rate(from_rate, to_rate)),
+			_ => None,
+		},
+	)
+}
+
+/// Main units of `to` per main unit of `from` at `date`.
+pub async fn get_cross_rate_at(
+	pool: &SqlitePool,
+	from: Currency,
+	to: Currency,
+	date: i64,
+) -> anyhow::Result<Option<i64>> {
+	Ok(
+		match (
+			get_rate_at(pool, from, date).await?,
+			get_rate_at(pool, to, date).await?,
+		) {
+			(Some(from_rate), Some(to_rate)) => Some(cross_rate(from_rate, to_rate)),
+			_ => None,
+		},
+	)
+}
+
+fn cross_rate(from_rate: i64, to_rate: i64) -> i64 {
+	(from_rate as i128 * RATE_SCALE as i128 / to_rate as i128) as i64
+}
+
+/// Formats a spread in basis points as a percentage.
+pub fn format_spread(spread: i64) -> String {
+	format!("{}.{:02}%", spread / 100, spread % 100)
+}
+
+/// Formats the relative change from `before` to `after` as a signed
+/// percentage.
+pub fn format_change(before: i64, after: i64) -> String {
+	let change = (after as i128 - before as i128) * BASIS_POINTS as i128 / before as i128;
+	let sign = if change < 0 { "-" } else { "+" };
+	let change = change.abs();
+	format!("{}{}.{:02}%", sign, change / 100, change % 100)
+}
+
+/// Stops `currency` from being exchanged. Returns whether it had a rate.
+pub async fn remove_rate(pool: &SqlitePool, currency: Currency) -> anyhow::Result<bool> {
+	Ok(
+		sqlx::query("DELETE FROM ExchangeRate WHERE guild_id=? AND currency=?")
+			.bind(currency.guild())
+			.bind(currency.code())
+			.execute(pool)
+			.await?
+			.rows_affected()
+			> 0,
+	)
+}
+
+/// Stores every rate `provider` knows for the currencies with the same code,
+/// in `guild` or in every guild when `None`, and returns how many were
+/// updated. Rates a director set are left alone.
+pub async fn refresh_rates(
+	pool: &SqlitePool,
+	provider: &dyn RateProvider,
+	guild: Option<GuildId>,
+) -> anyhow::Result<usize> {
+	let source = provider.name();
+	let mut updated = 0;
+
+	for (code, rate) in provider.fetch_rates().await? {
+		let currencies = match guild {
+			Some(guild) => Currency::try_from((guild, code.as_str()))
+				.into_iter()
+				.collect(),
+			None => currency::currencies_with_code(&code),
+		};
+
+		for currency in currencies {
+			if store_rate(pool, currency, rate, &source, false).await? {
+				updated += 1;
+			}
+		}
+	}
+
+	Ok(updated)
+}
+
+/// Refreshes the rates from `provider` now and then every `interval`.
+pub fn spawn_rate_refresh(pool: SqlitePool, provider: Arc<dyn RateProvider>, interval: Duration) {
+	tokio::spawn(async move {
+		let mut ticker = tokio::time::interval(interval);
+		loop {
+			ticker.tick().await;
+			if let Err(err) = refresh_rates(&pool, provider.as_ref(), None).await {
+				println!(
+					"Error while refreshing rates from {}: {}",
+					provider.name(),
+					err
+				);
+			}
+		}
+	});
+}
+
+/// The terms of an exchange, as shown to the user before it runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quote {
+	pub from: Currency,
+	pub to: Currency,
+	/// Debited amount in minor units of `from`.
+	pub value: i64,
+	/// Credited amount in minor units of `to`, after the spread.
+	pub credit: i64,
+	/// Main units of `to` per main unit of `from`, before the spread.
+	pub rate: i64,
+	pub spread: i64,
+}
+
+/// Converts `value` minor units of a currency worth `from_rate` into minor
+/// units of one worth `to_rate`, keeping `spread` basis points. `from_unit` and
+/// `to_unit` are the minor units in one main unit of each. Rounds down, so the
+/// bank never pays out more than the rates allow.
+fn convert(
+	value: i64,
+	from_rate: i64,
+	from_unit: i64,
+	to_rate: i64,
+	to_unit: i64,
+	spread: i64,
+) -> anyhow::Result<i64> {
+	let credit =
+		value as i128 * from_rate as i128 * to_unit as i128 * (BASIS_POINTS - spread) as i128
+			/ (to_rate as i128 * from_unit as i128 * BASIS_POINTS as i128);
+
+	Ok(i64::try_from(credit)?)
+}
+
+async fn fetch_quote(
+	conn: &mut SqliteConnection,
+	from: Currency,
+	to: Currency,
+	value: i64,
+	spread: i64,
+) -> anyhow::Result<Option<Quote>> {
+	let (from_rate, to_rate) = match (fetch_rate(conn, from).await?, fetch_rate(conn, to).await?) {
+		(Some(from_rate), Some(to_rate)) => (from_rate, to_rate),
+		_ => return Ok(None),
+	};
+
+	let credit = convert(
+		value,
+		from_rate,
+		CurrencyInfo::from(from).unit(),
+		to_rate,
+		CurrencyInfo::from(to).unit(),
+		spread,
+	)?;
+
+	Ok(Some(Quote {
+		from,
+		to,
+		value,
+		credit,
+		rate: cross_rate(from_rate, to_rate),
+		spread,
+	}))
+}
+
+/// Quotes an exchange of `value` minor units of `from` into `to`. `None` when
+/// either currency has no rate.
+pub async fn quote(
+	pool: &SqlitePool,
+	from: Currency,
+	to: Currency,
+	value: i64,
+	spread: i64,
+) -> anyhow::Result<Option<Quote>> {
+	let mut conn = pool.acquire().await?;
+	fetch_quote(&mut conn, from, to, value, spread).await
+}
+
+#[derive(Debug)]
+pub enum ExchangeStatus {
+	/// The debit and credit operation ids.
+	Exchanged(i64, i64),
+	NoRate,
+	RateChanged,
+	TooSmall,
+	InsuficientBalance,
+	Failed,
+}
+
+/// Runs the exchange described by `quote` for `account` with the bank as
+/// counterparty: `from` is sent to the bank and `to` is received from it in the
+/// same transaction, and the rate used is recorded in `Exchange`. Fails with
+/// `RateChanged` when the rates moved since the quote was shown.
+pub async fn exchange(pool: &SqlitePool, account: UserId, quote: Quote) -> ExchangeStatus {
+	let mut conn = match pool.acquire().await {
+		Ok(conn) => conn,
+		Err(_) => return ExchangeStatus::Failed,
+	};
+
+	let status = immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			match fetch_quote(conn, quote.from, quote.to, quote.value, quote.spread).await? {
+				Some(current) if current == quote => {}
+				Some(_) => return Ok(ExchangeStatus::RateChanged),
+				None => return Ok(ExchangeStatus::NoRate),
+			}
+
+			if quote.credit <= 0 {
+				return Ok(ExchangeStatus::TooSmall);
+			}
+
+			let debit_memo = format!("Exchange to {}", quote.to.code());
+			let debit_id = match try_transfer(
+				conn,
+				account,
+				BANK_ID,
+				quote.from,
+				quote.value,
+				Some(&debit_memo),
+			)
+			.await?
+			{
+				TransferStatus::Authorized(id) => id,
+				TransferStatus::InsuficientBalance => {
+					return Ok(ExchangeStatus::InsuficientBalance)
+				}
+				TransferStatus::BadValue | TransferStatus::Retired | TransferStatus::Failed => {
+					return Ok(ExchangeStatus::Failed)
+				}
+			};
+
+			let credit_memo = format!("Exchange from {}", quote.from.code());
+			let credit_id = match try_transfer(
+				conn,
+				BANK_ID,
+				account,
+				quote.to,
+				quote.credit,
+				Some(&credit_memo),
+			)
+			.await?
+			{
+				TransferStatus::Authorized(id) => id,
+				// Roll back the debit as well.
+				_ => anyhow::bail!("The bank could not credit the exchange."),
+			};
+
+			sqlx::query(
+				r#"INSERT INTO Exchange
+				(guild_id, account, from_currency, to_currency, value, credit, rate, spread, debit_id, credit_id, exchange_date)
+				VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+			)
+			.bind(quote.from.guild())
+			.bind(account)
+			.bind(quote.from.code())
+			.bind(quote.to.code())
+			.bind(quote.value)
+			.bind(quote.credit)
+			.bind(quote.rate)
+			.bind(quote.spread)
+			.bind(debit_id)
+			.bind(credit_id)
+			.bind(get_current_time())
+			.execute(conn)
+			.await?;
+
+			Ok(ExchangeStatus::Exchanged(debit_id, credit_id))
+		})
+	})
+	.await;
+
+	status.unwrap_or(ExchangeStatus::Failed)
+}
+
+/// The terms an executed exchange ran at.
+#[derive(Debug, sqlx::FromRow)]
+pub struct ExchangeRecord {
+	pub from_currency: String,
+	pub to_currency: String,
+	pub rate: i64,
+	pub spread: i64,
+}
+
+impl ExchangeRecord {
+	/// Explains the conversion, as in `1 USD = 0.925925925 EUR, spread 0.50%`.
+	pub fn describe(&self) -> String {
+		let mut description = format!(
+			"1 {} = {} {}",
+			self.from_currency,
+			format_rate(self.rate),
+			self.to_currency
+		);
+		if self.spread > 0 {
+			description.push_str(&format!(", spread {}", format_spread(self.spread)));
+		}
+
+		description
+	}
+}
+
+pub async fn get_exchange(pool: &SqlitePool, id: u32) -> anyhow::Result<Option<ExchangeRecord>> {
+	Ok(sqlx::query_as::<_, ExchangeRecord>(
+		"SELECT from_currency, to_currency, rate, spread FROM Exchange WHERE id=?",
+	)
+	.bind(id)
+	.fetch_optional(pool)
+	.await?)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_rate_is_fixed_point() {
+		assert_eq!(parse_rate("1"), Some(RATE_SCALE));
+		assert_eq!(parse_rate("1.08"), Some(1_080_000_000));
+		assert_eq!(parse_rate("0.5"), Some(500_000_000));
+		assert_eq!(parse_rate(".5"), Some(500_000_000));
+		assert_eq!(parse_rate("2."), Some(2 * RATE_SCALE));
+		assert_eq!(parse_rate("0.000000001"), Some(1));
+		assert_eq!(parse_rate("1.500000000000"), Some(1_500_000_000));
+		assert_eq!(parse_rate("5000"), Some(5000 * RATE_SCALE));
+	}
+
+	#[test]
+	fn parse_rate_rejects_malformed_and_non_positive_rates() {
+		for word in [
+			"",
+			".",
+			"0",
+			"0.0",
+			"-1",
+			"-0.5",
+			"+1",
+			"abc",
+			"1e3",
+			"1,5",
+			"1.2.3",
+			"inf",
+			"NaN",
+			"0.0000000001",
+			"99999999999",
+		] {
+			assert_eq!(parse_rate(word), None, "{}", word);
+		}
+	}
+
+	#[test]
+	fn format_rate_round_trips() {
+		for word in ["1", "1.08", "0.000000001", "123.456"] {
+			assert_eq!(format_rate(parse_rate(word).unwrap()), word);
+		}
+	}
+
+	#[test]
+	fn convert_applies_rates_and_units() {
+		let usd = parse_rate("1").unwrap();
+		let eur = parse_rate("1.25").unwrap();
+		let jpy = parse_rate("0.01").unwrap();
+
+		// 100.00 EUR is 125.00 USD.
+		assert_eq!(convert(10_000, eur, 100, usd, 100, 0).unwrap(), 12_500);
+		// 1.00 USD is 100 JPY, which has no minor units.
+		assert_eq!(convert(100, usd, 100, jpy, 1, 0).unwrap(), 100);
+		assert_eq!(convert(150, jpy, 1, usd, 100, 0).unwrap(), 150);
+	}
+
+	#[test]
+	fn convert_keeps_the_spread_in_basis_points() {
+		let usd = parse_rate("1").unwrap();
+
+		assert_eq!(convert(10_000, usd, 100, usd, 100, 150).unwrap(), 9_850);
+		assert_eq!(convert(10_000, usd, 100, usd, 100, 1).unwrap(), 9_999);
+		assert_eq!(
+			convert(10_000, usd, 100, usd, 100, BASIS_POINTS).unwrap(),
+			0
+		);
+	}
+
+	#[test]
+	fn convert_rounds_down() {
+		let usd = parse_rate("1").unwrap();
+		let eur = parse_rate("1.08").unwrap();
+
+		// 0.99 USD at 1.5% spread is 0.97515 USD.
+		assert_eq!(convert(99, usd, 100, usd, 100, 150).unwrap(), 97);
+		// 1.00 USD is 0.925925... EUR.
+		assert_eq!(convert(100, usd, 100, eur, 100, 0).unwrap(), 92);
+		// Too small to be worth a minor unit.
+		assert_eq!(convert(1, usd, 100, eur, 100, 150).unwrap(), 0);
+	}
+
+	#[test]
+	fn convert_rejects_credits_beyond_i64() {
+		let cheap = parse_rate("0.000000001").unwrap();
+		let dear = parse_rate("5000").unwrap();
+
+		assert!(convert(i64::MAX, dear, 1, cheap, 1, 0).is_err());
+	}
+
+	#[test]
+	fn parse_rate_file_reads_pairs() {
+		let contents = "# rates against USD\n\nusd 1\n  EUR 1.08  \nJPY 0.0067\n";
+
+		assert_eq!(
+			parse_rate_file(contents).unwrap(),
+			vec![
+				(String::from("USD"), RATE_SCALE),
+				(String::from("EUR"), 1_080_000_000),
+				(String::from("JPY"), 6_700_000),
+			]
+		);
+		assert!(parse_rate_file("").unwrap().is_empty());
+	}
+
+	#[test]
+	fn parse_rate_file_rejects_bad_lines() {
+		for contents in [
+			"USD 1\nEUR -1.08\n",
+			"USD 0\n",
+			"USD\n",
+			"USD 1 extra\n",
+			"USD one\n",
+		] {
+			assert!(parse_rate_file(contents).is_err(), "{}", contents);
+		}
+
+		let err = parse_rate_file("USD 1\nEUR -1.08\n").unwrap_err();
+		assert_eq!(err.to_string(), "Line 2: invalid rate `-1.08`.");
+	}
+
+	struct StaticRates(Vec<(String, i64)>);
+
+	#[async_trait]
+	impl RateProvider for StaticRates {
+		fn name(&self) -> String {
+			String::from("static")
+		}
+
+		async fn fetch_rates(&self) -> anyhow::Result<Vec<(String, i64)>> {
+			Ok(self.0.clone())
+		}
+	}
+
+	#[tokio::test]
+	async fn refresh_rates_stays_in_guild_and_keeps_director_rates() {
+		let pool = crate::database::test_pool().await;
+		let usd = crate::currency::register_test_currency(811, "USD", -2);
+		let eur = crate::currency::register_test_currency(811, "EUR", -2);
+		let other_usd = crate::currency::register_test_currency(812, "USD", -2);
+
+		set_rate(&pool, eur, parse_rate("1.2").unwrap(), DIRECTOR_SOURCE)
+			.await
+			.unwrap();
+		set_rate(&pool, other_usd, parse_rate("3").unwrap(), DIRECTOR_SOURCE)
+			.await
+			.unwrap();
+
+		let provider = StaticRates(vec![
+			(String::from("USD"), parse_rate("1").unwrap()),
+			(String::from("EUR"), parse_rate("1.08").unwrap()),
+		]);
+		let updated = refresh_rates(&pool, &provider, Some(811)).await.unwrap();
+		assert_eq!(updated, 1);
+
+		let mut conn = pool.acquire().await.unwrap();
+		assert_eq!(fetch_rate(&mut conn, usd).await.unwrap(), Some(RATE_SCALE));
+		assert_eq!(fetch_rate(&mut conn, eur).await.unwrap(), parse_rate("1.2"));
+		assert_eq!(
+			fetch_rate(&mut conn, other_usd).await.unwrap(),
+			parse_rate("3")
+		);
+	}
+
+	#[tokio::test]
+	async fn removed_rates_have_no_cross_rate() {
+		let pool = crate::database::test_pool().await;
+		let usd = crate::currency::register_test_currency(813, "USD", -2);
+		let eur = crate::currency::register_test_currency(813, "EUR", -2);
+
+		set_rate(&pool, usd, RATE_SCALE, DIRECTOR_SOURCE)
+			.await
+			.unwrap();
+		set_rate(&pool, eur, parse_rate("1.08").unwrap(), DIRECTOR_SOURCE)
+			.await
+			.unwrap();
+		assert!(get_cross_rate(&pool, usd, eur).await.unwrap().is_some());
+
+		assert!(remove_rate(&pool, eur).await.unwrap());
+		assert_eq!(get_cross_rate(&pool, usd, eur).await.unwrap(), None);
+	}
+}
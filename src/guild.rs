@@ -0,0 +1,221 @@
+use serenity::model::channel::Message;
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+
+use crate::currency;
+use crate::operation::{get_current_time, GuildId, UserId};
+use crate::settings;
+
+/// Records `guild` the first time it uses the bank and gives it the template
+/// currencies.
+pub async fn register_guild(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<()> {
+	let inserted =
+		sqlx::query("INSERT OR IGNORE INTO Guild (guild_id, created_date) VALUES (?, ?)")
+			.bind(guild)
+			.bind(get_current_time())
+			.execute(pool)
+			.await?
+			.rows_affected();
+
+	if inserted > 0 {
+		currency::seed_guild(pool, guild).await?;
+	}
+
+	Ok(())
+}
+
+/// The guild `msg` acts on: the guild it was sent in or, for direct messages,
+/// the one its author picked with `!guild`.
+pub async fn resolve_guild(pool: &SqlitePool, msg: &Message) -> anyhow::Result<Option<GuildId>> {
+	match msg.guild_id {
+		Some(guild_id) => {
+			let guild = *guild_id.as_u64() as GuildId;
+			register_guild(pool, guild).await?;
+			Ok(Some(guild))
+		}
+		None => settings::get_dm_guild(pool, *msg.author.id.as_u64() as UserId).await,
+	}
+}
+
+/// Every guild that was set up or has ledger rows.
+pub async fn get_guilds(pool: &SqlitePool) -> anyhow::Result<Vec<GuildId>> {
+	Ok(sqlx::query_as::<_, (GuildId,)>(
+		r#"SELECT guild_id FROM Guild
+		UNION
+		SELECT DISTINCT guild_id FROM Transfer
+		ORDER BY guild_id"#,
+	)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(|row| row.0)
+	.collect())
+}
+
+/// Prefix of the commands of guilds that did not pick one, and of every
+/// command sent in direct messages.
+pub const DEFAULT_PREFIX: &str = "!";
+
+pub const MAX_PREFIX_LENGTH: usize = 8;
+
+/// Prefixes already read from `GuildSettings`, since every message needs one
+/// before it is known to be a command.
+pub struct PrefixCache;
+
+impl TypeMapKey for PrefixCache {
+	type Value = HashMap<GuildId, String>;
+}
+
+pub async fn get_prefix(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<String> {
+	Ok(
+		sqlx::query_as::<_, (Option<String>,)>("SELECT prefix FROM GuildSettings WHERE guild_id=?")
+			.bind(guild)
+			.fetch_optional(pool)
+			.await?
+			.and_then(|row| row.0)
+			.unwrap_or_else(|| String::from(DEFAULT_PREFIX)),
+	)
+}
+
+/// Sets the command prefix of `guild`, or with `None` goes back to the default.
+pub async fn set_prefix(
+	pool: &SqlitePool,
+	guild: GuildId,
+	prefix: Option<&str>,
+) -> anyhow::Result<()> {
+	sqlx::query(
+		r#"INSERT INTO GuildSettings (guild_id, prefix)
+		VALUES (?, ?)
+		ON CONFLICT(guild_id) DO UPDATE SET prefix=excluded.prefix"#,
+	)
+	.bind(guild)
+	.bind(prefix)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+/// Channel receiving the bank announcements of `guild`.
+pub async fn get_announcement_channel(
+	pool: &SqlitePool,
+	guild: GuildId,
+) -> anyhow::Result<Option<i64>> {
+	Ok(sqlx::query_as::<_, (Option<i64>,)>(
+		"SELECT announcement_channel_id FROM GuildSettings WHERE guild_id=?",
+	)
+	.bind(guild)
+	.fetch_optional(pool)
+	.await?
+	.and_then(|row| row.0))
+}
+
+pub async fn set_announcement_channel(
+	pool: &SqlitePool,
+	guild: GuildId,
+	channel: Option<i64>,
+) -> anyhow::Result<()> {
+	sqlx::query(
+		r#"INSERT INTO GuildSettings (guild_id, announcement_channel_id)
+		VALUES (?, ?)
+		ON CONFLICT(guild_id) DO UPDATE SET announcement_channel_id=excluded.announcement_channel_id"#,
+	)
+	.bind(guild)
+	.bind(channel)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+/// Channel receiving live copies of the admin log of `guild`.
+pub async fn get_log_channel(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<Option<i64>> {
+	Ok(sqlx::query_as::<_, (Option<i64>,)>(
+		"SELECT log_channel_id FROM GuildSettings WHERE guild_id=?",
+	)
+	.bind(guild)
+	.fetch_optional(pool)
+	.await?
+	.and_then(|row| row.0))
+}
+
+pub async fn set_log_channel(
+	pool: &SqlitePool,
+	guild: GuildId,
+	channel: Option<i64>,
+) -> anyhow::Result<()> {
+	sqlx::query(
+		r#"INSERT INTO GuildSettings (guild_id, log_channel_id)
+		VALUES (?, ?)
+		ON CONFLICT(guild_id) DO UPDATE SET log_channel_id=excluded.log_channel_id"#,
+	)
+	.bind(guild)
+	.bind(channel)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+/// Channels where bank commands are allowed in `guild`. Empty when they are
+/// allowed everywhere.
+pub async fn get_allowed_channels(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<Vec<i64>> {
+	Ok(sqlx::query_as::<_, (i64,)>(
+		r#"SELECT channel_id FROM AllowedChannel
+		WHERE guild_id=?
+		ORDER BY added_date"#,
+	)
+	.bind(guild)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(|row| row.0)
+	.collect())
+}
+
+/// Returns false when the channel was already allowed.
+pub async fn allow_channel(
+	pool: &SqlitePool,
+	guild: GuildId,
+	channel: i64,
+) -> anyhow::Result<bool> {
+	let inserted = sqlx::query(
+		r#"INSERT OR IGNORE INTO AllowedChannel (guild_id, channel_id, added_date)
+		VALUES (?, ?, ?)"#,
+	)
+	.bind(guild)
+	.bind(channel)
+	.bind(get_current_time())
+	.execute(pool)
+	.await?
+	.rows_affected();
+
+	Ok(inserted > 0)
+}
+
+/// Returns false when the channel was not allowed.
+pub async fn disallow_channel(
+	pool: &SqlitePool,
+	guild: GuildId,
+	channel: i64,
+) -> anyhow::Result<bool> {
+	let deleted = sqlx::query("DELETE FROM AllowedChannel WHERE guild_id=? AND channel_id=?")
+		.bind(guild)
+		.bind(channel)
+		.execute(pool)
+		.await?
+		.rows_affected();
+
+	Ok(deleted > 0)
+}
+
+/// Allows bank commands in every channel of `guild` again.
+pub async fn clear_allowed_channels(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<()> {
+	sqlx::query("DELETE FROM AllowedChannel WHERE guild_id=?")
+		.bind(guild)
+		.execute(pool)
+		.await?;
+
+	Ok(())
+}
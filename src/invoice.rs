@@ -0,0 +1,191 @@
+use sqlx::{SqliteConnection, SqlitePool};
+
+use crate::currency::{self, Currency};
+use crate::database::immediate_transaction;
+use crate::operation::{get_current_time, try_transfer, GuildId, TransferStatus, UserId};
+
+/// Invoices expire this long after they are created unless the requester
+/// picks another date.
+pub const DEFAULT_INVOICE_EXPIRY_DAYS: u64 = 7;
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct Invoice {
+	pub id: u32,
+	pub guild_id: GuildId,
+	pub currency: String,
+	pub requester: UserId,
+	pub payer: UserId,
+	pub value: i64,
+	pub description: Option<String>,
+	pub expiry_date: i64,
+	pub status: String,
+}
+
+#[derive(Debug)]
+pub enum InvoiceStatus {
+	Paid(i64),
+	Declined,
+	NotFound,
+	NotPayer,
+	Closed,
+	Expired,
+	InsuficientBalance,
+	/// The currency was retired after the invoice was sent.
+	Retired,
+	Failed,
+}
+
+pub async fn create_invoice(
+	pool: &SqlitePool,
+	requester: UserId,
+	payer: UserId,
+	currency: Currency,
+	value: i64,
+	description: Option<&str>,
+	expiry_date: i64,
+) -> anyhow::Result<u32> {
+	Ok(sqlx::query(
+		r#"INSERT INTO Invoice
+		(guild_id, currency, requester, payer, value, description, created_date, expiry_date)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?)"#,
+	)
+	.bind(currency.guild())
+	.bind(currency.code())
+	.bind(requester)
+	.bind(payer)
+	.bind(value)
+	.bind(description)
+	.bind(get_current_time())
+	.bind(expiry_date)
+	.execute(pool)
+	.await?
+	.last_insert_rowid() as u32)
+}
+
+/// Open, unexpired invoices of `guild` that `account` either sent or has to
+/// pay.
+pub async fn get_open_invoices(
+	pool: &SqlitePool,
+	guild: GuildId,
+	account: UserId,
+) -> anyhow::Result<Vec<Invoice>> {
+	Ok(sqlx::query_as::<_, Invoice>(
+		r#"SELECT * FROM Invoice
+		WHERE guild_id=? AND (requester=? OR payer=?) AND status='open' AND expiry_date > ?
+		ORDER BY id"#,
+	)
+	.bind(guild)
+	.bind(account)
+	.bind(account)
+	.bind(get_current_time())
+	.fetch_all(pool)
+	.await?)
+}
+
+/// Pays invoice `id` on behalf of `payer` with a regular transfer to the
+/// requester. The transfer memo names the invoice and the invoice keeps the
+/// transfer id.
+pub async fn pay_invoice(pool: &SqlitePool, id: u32, payer: UserId) -> InvoiceStatus {
+	let mut conn = match pool.acquire().await {
+		Ok(conn) => conn,
+		Err(_) => return InvoiceStatus::Failed,
+	};
+
+	let status = immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			let invoice = match fetch_open_invoice(conn, id, payer).await? {
+				Ok(invoice) => invoice,
+				Err(status) => return Ok(status),
+			};
+
+			let memo = match &invoice.description {
+				Some(description) => format!("Invoice #{}: {}", invoice.id, description),
+				None => format!("Invoice #{}", invoice.id),
+			};
+
+			let currency = Currency::try_from((invoice.guild_id, invoice.currency.as_str()))?;
+			if currency::is_retired(conn, currency).await? {
+				return Ok(InvoiceStatus::Retired);
+			}
+
+			let transfer_id = match try_transfer(
+				conn,
+				invoice.payer,
+				invoice.requester,
+				currency,
+				invoice.value,
+				Some(&memo),
+			)
+			.await?
+			{
+				TransferStatus::Authorized(transfer_id) => transfer_id,
+				TransferStatus::InsuficientBalance => return Ok(InvoiceStatus::InsuficientBalance),
+				TransferStatus::BadValue | TransferStatus::Retired | TransferStatus::Failed => {
+					return Ok(InvoiceStatus::Failed)
+				}
+			};
+
+			sqlx::query("UPDATE Invoice SET status='paid', transfer_id=? WHERE id=?")
+				.bind(transfer_id)
+				.bind(id)
+				.execute(conn)
+				.await?;
+
+			Ok(InvoiceStatus::Paid(transfer_id))
+		})
+	})
+	.await;
+
+	status.unwrap_or(InvoiceStatus::Failed)
+}
+
+pub async fn decline_invoice(pool: &SqlitePool, id: u32, payer: UserId) -> InvoiceStatus {
+	let mut conn = match pool.acquire().await {
+		Ok(conn) => conn,
+		Err(_) => return InvoiceStatus::Failed,
+	};
+
+	let status = immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			if let Err(status) = fetch_open_invoice(conn, id, payer).await? {
+				return Ok(status);
+			}
+
+			sqlx::query("UPDATE Invoice SET status='declined' WHERE id=?")
+				.bind(id)
+				.execute(conn)
+				.await?;
+
+			Ok(InvoiceStatus::Declined)
+		})
+	})
+	.await;
+
+	status.unwrap_or(InvoiceStatus::Failed)
+}
+
+/// Loads invoice `id` if `payer` may still settle it, or the reason why not.
+async fn fetch_open_invoice(
+	conn: &mut SqliteConnection,
+	id: u32,
+	payer: UserId,
+) -> anyhow::Result<Result<Invoice, InvoiceStatus>> {
+	let invoice = match sqlx::query_as::<_, Invoice>("SELECT * FROM Invoice WHERE id=?")
+		.bind(id)
+		.fetch_optional(&mut *conn)
+		.await?
+	{
+		Some(invoice) => invoice,
+		None => return Ok(Err(InvoiceStatus::NotFound)),
+	};
+
+	Ok(if invoice.payer != payer {
+		Err(InvoiceStatus::NotPayer)
+	} else if invoice.status != "open" {
+		Err(InvoiceStatus::Closed)
+	} else if invoice.expiry_date <= get_current_time() {
+		Err(InvoiceStatus::Expired)
+	} else {
+		Ok(invoice)
+	})
+}
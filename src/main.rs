@@ -1,48 +1,328 @@
+use adminlog::Outcome;
+use commands::Invocation;
+use database::{Database, DatabaseConfig};
 use dotenv::dotenv;
+use exchange::{ExchangeSpread, RateSource};
+use guild::PrefixCache;
+use operation::{GuildId, UserId};
+use pending::CancelWindow;
+use permission::{Access, Permission};
+use router::{Action, Command};
+use std::collections::HashMap;
 use std::env;
 
 use serenity::async_trait;
 use serenity::model::channel::Message;
 use serenity::model::gateway::Ready;
+use serenity::model::guild::Guild;
+use serenity::model::id::{ChannelId, RoleId};
+use serenity::model::interactions::application_command::ApplicationCommandInteraction;
+use serenity::model::interactions::{Interaction, InteractionResponseType};
+use serenity::model::user::User;
 use serenity::prelude::*;
 
+mod adminlog;
+mod audit;
+mod chain;
 mod commands;
 mod currency;
+mod database;
+mod exchange;
+mod guild;
+mod invoice;
 mod operation;
+mod pending;
+mod permission;
+mod router;
+mod settings;
+mod slash;
 mod stat;
 
 struct Handler {
-	director_id: u64,
+	/// The bot operator, who holds every permission in every guild.
+	operator_id: u64,
+}
+
+impl Handler {
+	/// Runs a command that acts on the ledger of `guild`, once its author is
+	/// allowed to.
+	async fn dispatch(
+		&self,
+		ctx: &Context,
+		msg: &Message,
+		guild: GuildId,
+		command: &Command,
+	) -> Option<anyhow::Error> {
+		let pool = database::get_pool(ctx).await;
+		let roles = msg.member.as_ref().map(|member| member.roles.as_slice());
+		let access =
+			match permission::get_access(ctx, &pool, &msg.author, roles, guild, self.operator_id)
+				.await
+			{
+				Ok(access) => access,
+				Err(err) => return Some(err),
+			};
+
+		let permission = match command.permission {
+			Some(permission) => permission,
+			None => {
+				return self
+					.execute(ctx, msg, guild, command.action, &access)
+					.await
+					.err()
+			}
+		};
+
+		let inv = Invocation::Message(msg);
+		if !access.allows(permission) {
+			commands::permission_denied(ctx, msg, permission).await;
+			self.log(ctx, &inv, guild, &msg.content, Outcome::Denied)
+				.await;
+			return None;
+		}
+
+		let result = self.execute(ctx, msg, guild, command.action, &access).await;
+		let outcome = result.as_ref().copied().unwrap_or(Outcome::Failed);
+		self.log(ctx, &inv, guild, &msg.content, outcome).await;
+
+		result.err()
+	}
+
+	/// Whether bank commands may run in `channel`. Those allowed to configure the
+	/// bank may run them anywhere, so they cannot lock themselves out.
+	async fn channel_allowed(
+		&self,
+		ctx: &Context,
+		user: &User,
+		roles: Option<&[RoleId]>,
+		guild_id: Option<serenity::model::id::GuildId>,
+		channel: ChannelId,
+	) -> anyhow::Result<bool> {
+		// Direct messages are always allowed.
+		let guild = match guild_id {
+			Some(guild_id) => *guild_id.as_u64() as GuildId,
+			None => return Ok(true),
+		};
+
+		let pool = database::get_pool(ctx).await;
+		let channels = guild::get_allowed_channels(&pool, guild).await?;
+		if channels.is_empty() || channels.contains(&(*channel.as_u64() as i64)) {
+			return Ok(true);
+		}
+
+		let access =
+			permission::get_access(ctx, &pool, user, roles, guild, self.operator_id).await?;
+		Ok(access.allows(Permission::ManageGuild))
+	}
+
+	/// Records a privileged command in the admin log and its live channel. The
+	/// invoker is warned when the record could not be written, since the command
+	/// has already run by then.
+	async fn log(
+		&self,
+		ctx: &Context,
+		inv: &Invocation<'_>,
+		guild: GuildId,
+		content: &str,
+		outcome: Outcome,
+	) {
+		let pool = database::get_pool(ctx).await;
+		let user = *inv.author().id.as_u64() as UserId;
+
+		let entry = match adminlog::log_command(&pool, guild, user, content, outcome).await {
+			Ok(entry) => entry,
+			Err(err) => {
+				println!("Error while logging: {}: {}", content, err);
+				inv.say(
+					ctx,
+					"**Warning:** this command could not be written to the admin log.",
+				)
+				.await;
+				return;
+			}
+		};
+
+		if let Err(err) = commands::publish_admin_log(ctx, &entry).await {
+			println!("Error while publishing the admin log: {}: {}", content, err);
+		}
+	}
+
+	/// Runs a slash command with the same permission checks and logging as its
+	/// prefix command.
+	async fn slash_command(
+		&self,
+		ctx: &Context,
+		inv: &Invocation<'_>,
+		interaction: &ApplicationCommandInteraction,
+	) -> anyhow::Result<()> {
+		// Slash commands are only registered in guilds.
+		let guild = match interaction.guild_id {
+			Some(guild_id) => *guild_id.as_u64() as GuildId,
+			None => return Ok(()),
+		};
+
+		// Deferred before anything that can fail, so the error reply always has
+		// a response to replace.
+		interaction
+			.create_interaction_response(&ctx.http, |r| {
+				r.kind(InteractionResponseType::DeferredChannelMessageWithSource)
+			})
+			.await?;
+
+		let roles = interaction
+			.member
+			.as_ref()
+			.map(|member| member.roles.as_slice());
+		let allowed = self
+			.channel_allowed(
+				ctx,
+				&interaction.user,
+				roles,
+				interaction.guild_id,
+				interaction.channel_id,
+			)
+			.await?;
+		if !allowed {
+			inv.say(ctx, "Bank commands are not allowed in this channel.")
+				.await;
+			return Ok(());
+		}
+
+		let pool = database::get_pool(ctx).await;
+		guild::register_guild(&pool, guild).await?;
+
+		let permission =
+			router::find(&interaction.data.name).and_then(|command| command.permission);
+		let permission = match permission {
+			Some(permission) => permission,
+			None => {
+				return slash::execute(ctx, inv, interaction, guild)
+					.await
+					.map(|_| ())
+			}
+		};
+
+		let access = permission::get_access(
+			ctx,
+			&pool,
+			&interaction.user,
+			roles,
+			guild,
+			self.operator_id,
+		)
+		.await?;
+
+		let content = slash::describe(interaction);
+		if !access.allows(permission) {
+			inv.say(ctx, &commands::denial_message(permission)).await;
+			self.log(ctx, inv, guild, &content, Outcome::Denied).await;
+			return Ok(());
+		}
+
+		let result = slash::execute(ctx, inv, interaction, guild).await;
+		let outcome = result.as_ref().copied().unwrap_or(Outcome::Failed);
+		self.log(ctx, inv, guild, &content, outcome).await;
+
+		result.map(|_| ())
+	}
+
+	async fn execute(
+		&self,
+		ctx: &Context,
+		msg: &Message,
+		guild: GuildId,
+		action: Action,
+		access: &Access,
+	) -> anyhow::Result<Outcome> {
+		let result = match action {
+			Action::Balance => commands::get_balance_command(ctx, msg, guild).await,
+			Action::Transfer => commands::transfer_command(ctx, msg, guild).await,
+			Action::Statement => commands::get_statement_command(ctx, msg, guild).await,
+			Action::Stat => commands::get_stat_command(ctx, msg, guild).await,
+			Action::Receipt => {
+				let view_any = access.allows(Permission::ViewReceipts);
+				commands::receipt_command(ctx, msg, guild, view_any).await
+			}
+			Action::Request => commands::request_command(ctx, msg, guild).await,
+			Action::Invoices => commands::invoices_command(ctx, msg, guild).await,
+			Action::Rates => commands::rates_command(ctx, msg, guild).await,
+			Action::Exchange => commands::exchange_command(ctx, msg, guild).await,
+			Action::Roles => commands::roles_command(ctx, msg, guild).await,
+			// These tell whether they went through, since they reply to refusals
+			// without failing.
+			Action::Create => return commands::create_deposit_command(ctx, msg, guild).await,
+			Action::Reverse => return commands::reverse_command(ctx, msg, guild).await,
+			Action::Rebuild => commands::rebuild_balances_command(ctx, msg, guild).await,
+			Action::Audit => commands::audit_command(ctx, msg, guild).await,
+			Action::PublishHead => commands::publish_head_command(ctx, msg, guild).await,
+			Action::Rate => commands::rate_command(ctx, msg, guild).await,
+			Action::Currency => commands::currency_command(ctx, msg, guild).await,
+			Action::Grant => commands::grant_command(ctx, msg, guild, true).await,
+			Action::Revoke => commands::grant_command(ctx, msg, guild, false).await,
+			Action::AuditLog => commands::auditlog_command(ctx, msg, guild).await,
+			Action::LogChannel => commands::logchannel_command(ctx, msg, guild).await,
+			Action::Config => commands::config_command(ctx, msg, guild).await,
+			// These do not need a guild and never reach here.
+			Action::Ping
+			| Action::Help
+			| Action::Timezone
+			| Action::Confirm
+			| Action::Guild
+			| Action::Cancel => Ok(()),
+		};
+
+		result.map(|()| Outcome::Done)
+	}
 }
 
 #[async_trait]
 impl EventHandler for Handler {
 	async fn message(&self, ctx: Context, msg: Message) {
-		if msg.content == "!ping" {
-			if let Err(why) = msg.channel_id.say(&ctx.http, "Pong!").await {
-				println!("Error sending message: {:?}", why);
-			}
+		let prefix = commands::command_prefix(&ctx, msg.guild_id).await;
+		let command = match router::parse(&msg.content, &prefix) {
+			Some(command) => command,
+			None => return,
+		};
 
-			return;
+		let roles = msg.member.as_ref().map(|member| member.roles.as_slice());
+		match self
+			.channel_allowed(&ctx, &msg.author, roles, msg.guild_id, msg.channel_id)
+			.await
+		{
+			Ok(true) => {}
+			Ok(false) => return,
+			Err(err) => {
+				println!("Error while checking the channel: {}: {}", msg.content, err);
+				return;
+			}
 		}
 
-		let error = if msg.content.starts_with("!balance") {
-			commands::get_balance_command(&ctx, &msg).await.err()
-		} else if msg.content.starts_with("!transfer") {
-			commands::transfer_command(&ctx, &msg).await;
-			None
-		} else if msg.content.starts_with("!statement") {
-			commands::get_statement_command(&ctx, &msg).await.err()
-		} else if msg.content.starts_with("!stat ") {
-			commands::get_stat_command(&ctx, &msg).await;
-			None
-		} else if msg.content.starts_with("!create") {
-			if *msg.author.id.as_u64() == self.director_id {
-				commands::create_deposit_command(&ctx, &msg).await;
+		let error = match command.action {
+			Action::Ping => msg
+				.channel_id
+				.say(&ctx.http, "Pong!")
+				.await
+				.err()
+				.map(anyhow::Error::from),
+			Action::Help => commands::help_command(&ctx, &msg).await.err(),
+			Action::Timezone => commands::timezone_command(&ctx, &msg).await.err(),
+			Action::Confirm => commands::confirm_command(&ctx, &msg).await.err(),
+			Action::Guild => commands::guild_command(&ctx, &msg).await.err(),
+			Action::Cancel => commands::cancel_command(&ctx, &msg).await.err(),
+			_ => {
+				let pool = database::get_pool(&ctx).await;
+				match guild::resolve_guild(&pool, &msg).await {
+					Ok(Some(guild)) => self.dispatch(&ctx, &msg, guild, command).await,
+					Ok(None) => {
+						msg.channel_id
+							.say(&ctx.http, "Pick a server with `!guild` first.")
+							.await
+							.ok();
+						None
+					}
+					Err(err) => Some(err),
+				}
 			}
-			None
-		} else {
-			None
 		};
 
 		if let Some(err) = error {
@@ -55,8 +335,46 @@ impl EventHandler for Handler {
 		}
 	}
 
-	async fn ready(&self, _: Context, ready: Ready) {
+	async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+		match interaction {
+			Interaction::MessageComponent(component)
+				if component.data.custom_id.starts_with("invoice_") =>
+			{
+				if let Err(err) = commands::invoice_interaction(&ctx, &component).await {
+					println!("Error while handling invoice button: {}", err);
+				}
+			}
+			Interaction::ApplicationCommand(command) => {
+				let inv = Invocation::slash(&command);
+				if let Err(err) = self.slash_command(&ctx, &inv, &command).await {
+					inv.say(&ctx, "> Critical Error!").await;
+					println!("Error while executing: /{}: {}", command.data.name, err);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	async fn guild_create(&self, ctx: Context, guild: Guild) {
+		let guild = *guild.id.as_u64() as GuildId;
+		let pool = database::get_pool(&ctx).await;
+
+		let result = match guild::register_guild(&pool, guild).await {
+			Ok(()) => slash::register_commands(&ctx, guild).await,
+			Err(err) => Err(err),
+		};
+		if let Err(err) = result {
+			println!("Error while setting up guild {}: {}", guild, err);
+		}
+	}
+
+	async fn ready(&self, ctx: Context, ready: Ready) {
 		println!("{} is connected!", ready.user.name);
+
+		// Settling is idempotent, so resuming again after a reconnect is harmless.
+		if let Err(err) = commands::resume_pending_transfers(&ctx).await {
+			println!("Error while resuming pending transfers: {}", err);
+		}
 	}
 }
 
@@ -64,21 +382,81 @@ impl EventHandler for Handler {
 async fn main() {
 	dotenv().ok();
 
+	let database_config = DatabaseConfig::from_env().expect("Invalid database configuration.");
+
+	// The audit reads the database as it is: migrating or sealing it first would
+	// rewrite the evidence it is meant to check.
+	if env::args().nth(1).as_deref() == Some("audit") {
+		let pool = database_config
+			.connect_read_only()
+			.await
+			.expect("Could not open the database.");
+		let version = database::read_schema_version(&pool)
+			.await
+			.expect("Could not read the schema version.");
+		if version != database::SCHEMA_VERSION {
+			println!(
+				"Database schema version {} does not match this binary ({}). Audit it with a matching binary.",
+				version,
+				database::SCHEMA_VERSION
+			);
+			std::process::exit(2);
+		}
+
+		currency::load_registry(&pool)
+			.await
+			.expect("Could not load the currencies.");
+		let sound = audit::run_offline(&pool)
+			.await
+			.expect("Could not audit the ledger.");
+		std::process::exit(if sound { 0 } else { 1 });
+	}
+
+	let pool = database_config
+		.connect()
+		.await
+		.expect("Could not open the database.");
+	database::migrate(&pool, database_config.default_guild)
+		.await
+		.expect("Could not migrate the database.");
+	currency::load_registry(&pool)
+		.await
+		.expect("Could not load the currencies.");
+
 	let token = env::var("BANK_DISCORD_TOKEN").expect("Expected a token in the environment.");
-	let director_id = env::var("DIRECTOR_ID").expect("Expected an admin ID.");
-	let director_id = director_id
+	let operator_id = env::var("DIRECTOR_ID").expect("Expected an admin ID.");
+	let operator_id = operator_id
 		.parse::<u64>()
 		.expect("Expected an integer admin ID.");
 
-	let intents = GatewayIntents::GUILD_MESSAGES
+	let cancel_window = pending::cancel_window_from_env().expect("Invalid cancel window.");
+	let spread = exchange::spread_from_env().expect("Invalid exchange spread.");
+	let rate_provider =
+		exchange::provider_from_env().expect("Invalid rate provider configuration.");
+
+	let intents = GatewayIntents::GUILDS
+		| GatewayIntents::GUILD_MESSAGES
 		| GatewayIntents::DIRECT_MESSAGES
 		| GatewayIntents::MESSAGE_CONTENT;
 
 	let mut client = Client::builder(&token, intents)
-		.event_handler(Handler { director_id })
+		.event_handler(Handler { operator_id })
+		.type_map_insert::<Database>(pool.clone())
+		.type_map_insert::<CancelWindow>(cancel_window)
+		.type_map_insert::<ExchangeSpread>(spread)
+		.type_map_insert::<PrefixCache>(HashMap::new())
 		.await
 		.expect("Err creating client");
 
+	if let Some((provider, refresh)) = rate_provider {
+		client
+			.data
+			.write()
+			.await
+			.insert::<RateSource>(provider.clone());
+		exchange::spawn_rate_refresh(pool, provider, refresh);
+	}
+
 	if let Err(why) = client.start().await {
 		println!("Client error: {:?}", why);
 	}
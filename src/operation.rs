@@ -1,21 +1,29 @@
-use sqlx::{Connection, Executor, SqliteConnection};
+use sqlx::{Executor, QueryBuilder, Sqlite, SqliteConnection, SqlitePool};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::chain;
 use crate::currency::{Currency, CurrencyInfo};
+use crate::database::immediate_transaction;
 
 #[derive(Debug)]
 pub struct Transfer {
+	pub id: u32,
 	pub currency: Currency,
 	pub from_account: i64,
 	pub to_account: i64,
 	pub balance: i64,
 	pub value: i64,
 	pub date: i64,
+	pub description: Option<String>,
+	pub reverses: Option<u32>,
+	pub reversed_by: Option<u32>,
+	pub exchange_id: Option<u32>,
 }
 
 #[derive(Debug, sqlx::FromRow)]
 pub struct LedgerRow {
 	pub id: u32,
+	pub guild_id: GuildId,
 	pub currency: String,
 	pub from_account: UserId,
 	pub to_account: UserId,
@@ -23,14 +31,31 @@ pub struct LedgerRow {
 	pub to_balance: i64,
 	pub value: i64,
 	pub transfer_date: i64,
-	pub description: String,
+	pub description: Option<String>,
+	pub previous_hash: Option<String>,
+	pub hash: Option<String>,
+	pub reverses: Option<u32>,
+	pub hash_version: Option<i64>,
+	/// Id of the row reversing this one. Not a column; only filled by queries
+	/// that select it.
+	#[sqlx(default)]
+	pub reversed_by: Option<u32>,
+	/// Id of the exchange this row is a leg of. Not a column either.
+	#[sqlx(default)]
+	pub exchange_id: Option<u32>,
 }
 
+/// Longest memo that fits in the `description` column.
+pub const MAX_DESCRIPTION_LENGTH: usize = 255;
+
 pub type UserId = i64;
 
+/// Discord guild a ledger belongs to.
+pub type GuildId = i64;
+
 pub const BANK_ID: UserId = 0;
 
-fn get_current_time() -> i64 {
+pub fn get_current_time() -> i64 {
 	let start = SystemTime::now();
 	let since_the_epoch = start
 		.duration_since(UNIX_EPOCH)
@@ -39,84 +64,288 @@ fn get_current_time() -> i64 {
 	since_the_epoch.as_secs() as i64 * 1000 + since_the_epoch.subsec_nanos() as i64 / 1_000_000
 }
 
-pub async fn get_balance(account: i64, currency: Currency) -> anyhow::Result<i64> {
+pub async fn get_balance(
+	pool: &SqlitePool,
+	account: i64,
+	currency: Currency,
+) -> anyhow::Result<i64> {
+	let mut conn = pool.acquire().await?;
+	fetch_balance(&mut conn, account, currency).await
+}
+
+async fn fetch_balance(
+	conn: &mut SqliteConnection,
+	account: i64,
+	currency: Currency,
+) -> anyhow::Result<i64> {
 	let currency_info = CurrencyInfo::from(currency);
 
-	let mut conn = SqliteConnection::connect("sqlite://bank_database.db").await?;
-	let ledger_row = sqlx::query_as::<_, LedgerRow>(
-		r#"SELECT * FROM Transfer
-		WHERE currency=? AND (from_account=? OR to_account=?)
-		ORDER BY id DESC"#,
+	let balance = sqlx::query_as::<_, (i64,)>(
+		r#"SELECT balance FROM Account
+		WHERE guild_id=? AND account=? AND currency=?"#,
 	)
-	.bind(currency_info.code)
-	.bind(account)
+	.bind(currency.guild())
 	.bind(account)
-	.fetch_optional(&mut conn)
+	.bind(currency_info.code)
+	.fetch_optional(conn)
 	.await?;
 
-	Ok(match ledger_row {
-		Some(row) => Transfer::try_from((account, row))?.balance,
-		None => 0,
-	})
+	Ok(balance.map(|row| row.0).unwrap_or(0))
 }
 
-pub async fn get_statement(account: i64, currency: Currency) -> anyhow::Result<Vec<Transfer>> {
+async fn store_balance(
+	conn: &mut SqliteConnection,
+	account: i64,
+	currency: Currency,
+	balance: i64,
+) -> anyhow::Result<()> {
 	let currency_info = CurrencyInfo::from(currency);
 
-	let mut conn = SqliteConnection::connect("sqlite://bank_database.db").await?;
-	let ledger_rows = sqlx::query_as::<_, LedgerRow>(
-		r#"SELECT * FROM Transfer
-		WHERE currency=? AND (from_account=? OR to_account=?)
-		ORDER BY id DESC"#,
+	sqlx::query(
+		r#"INSERT INTO Account (guild_id, account, currency, balance)
+		VALUES (?, ?, ?, ?)
+		ON CONFLICT(guild_id, account, currency) DO UPDATE SET balance=excluded.balance"#,
 	)
-	.bind(currency_info.code)
+	.bind(currency.guild())
 	.bind(account)
-	.bind(account)
-	.fetch_all(&mut conn)
+	.bind(currency_info.code)
+	.bind(balance)
+	.execute(conn)
 	.await?;
 
+	Ok(())
+}
+
+/// Regenerates the `Account` rows of `guild` from the balance snapshots of the
+/// latest ledger row of every account.
+pub async fn rebuild_balances(pool: &SqlitePool, guild: GuildId) -> anyhow::Result<()> {
+	let mut conn = pool.acquire().await?;
+
+	immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			sqlx::query("DELETE FROM Account WHERE guild_id=?")
+				.bind(guild)
+				.execute(&mut *conn)
+				.await?;
+			sqlx::query(
+				r#"INSERT INTO Account (guild_id, account, currency, balance)
+				SELECT guild_id, account, currency, balance FROM (
+					SELECT guild_id, account, currency, balance,
+						ROW_NUMBER() OVER (PARTITION BY account, currency ORDER BY id DESC) AS position
+					FROM (
+						SELECT id, guild_id, currency, from_account AS account, from_balance AS balance
+						FROM Transfer WHERE guild_id=?
+						UNION ALL
+						SELECT id, guild_id, currency, to_account AS account, to_balance AS balance
+						FROM Transfer WHERE guild_id=?
+					)
+				)
+				WHERE position = 1"#,
+			)
+			.bind(guild)
+			.bind(guild)
+			.execute(conn)
+			.await?;
+			Ok(())
+		})
+	})
+	.await
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+	Deposit,
+	Sent,
+	Received,
+	Charge,
+	/// Either leg of a currency exchange with the bank.
+	Exchange,
+}
+
+/// SQL condition matching the `Transfer` rows that are legs of an exchange.
+pub const EXCHANGE_LEG: &str = "EXISTS (SELECT 1 FROM Exchange WHERE Exchange.debit_id = Transfer.id OR Exchange.credit_id = Transfer.id)";
+
+/// Restricts which ledger rows show up in a statement. Empty `kinds` means
+/// every kind, and `until` is exclusive.
+#[derive(Debug, Clone, Default)]
+pub struct StatementFilter {
+	pub kinds: Vec<OperationKind>,
+	pub counterparty: Option<UserId>,
+	pub since: Option<i64>,
+	pub until: Option<i64>,
+}
+
+fn push_statement_filter(
+	builder: &mut QueryBuilder<'_, Sqlite>,
+	account: i64,
+	currency: Currency,
+	filter: &StatementFilter,
+) {
+	let currency_info = CurrencyInfo::from(currency);
+
+	builder
+		.push(" WHERE guild_id=")
+		.push_bind(currency.guild())
+		.push(" AND currency=")
+		.push_bind(currency_info.code.into_owned())
+		.push(" AND (from_account=")
+		.push_bind(account)
+		.push(" OR to_account=")
+		.push_bind(account)
+		.push(")");
+
+	if !filter.kinds.is_empty() {
+		builder.push(" AND (");
+		for (index, kind) in filter.kinds.iter().enumerate() {
+			if index > 0 {
+				builder.push(" OR ");
+			}
+
+			match kind {
+				OperationKind::Deposit => builder
+					.push("(from_account=")
+					.push_bind(BANK_ID)
+					.push(" AND value > 0 AND NOT ")
+					.push(EXCHANGE_LEG)
+					.push(")"),
+				OperationKind::Charge => builder
+					.push("(((from_account=")
+					.push_bind(BANK_ID)
+					.push(" AND value < 0) OR to_account=")
+					.push_bind(BANK_ID)
+					.push(") AND NOT ")
+					.push(EXCHANGE_LEG)
+					.push(")"),
+				OperationKind::Sent => builder
+					.push("(from_account=")
+					.push_bind(account)
+					.push(" AND to_account!=")
+					.push_bind(BANK_ID)
+					.push(")"),
+				OperationKind::Received => builder
+					.push("(to_account=")
+					.push_bind(account)
+					.push(" AND from_account!=")
+					.push_bind(BANK_ID)
+					.push(")"),
+				OperationKind::Exchange => builder.push(EXCHANGE_LEG),
+			};
+		}
+		builder.push(")");
+	}
+
+	if let Some(counterparty) = filter.counterparty {
+		builder
+			.push(" AND (from_account=")
+			.push_bind(counterparty)
+			.push(" OR to_account=")
+			.push_bind(counterparty)
+			.push(")");
+	}
+
+	if let Some(since) = filter.since {
+		builder.push(" AND transfer_date >= ").push_bind(since);
+	}
+
+	if let Some(until) = filter.until {
+		builder.push(" AND transfer_date < ").push_bind(until);
+	}
+}
+
+/// Fetches one page of an account statement, newest operations first.
+pub async fn get_statement(
+	pool: &SqlitePool,
+	account: i64,
+	currency: Currency,
+	filter: &StatementFilter,
+	offset: i64,
+	limit: i64,
+) -> anyhow::Result<Vec<Transfer>> {
+	let mut builder = QueryBuilder::new(
+		r#"SELECT *, (
+			SELECT reversal.id FROM Transfer AS reversal
+			WHERE reversal.reverses = Transfer.id
+		) AS reversed_by, (
+			SELECT Exchange.id FROM Exchange
+			WHERE Exchange.debit_id = Transfer.id OR Exchange.credit_id = Transfer.id
+		) AS exchange_id
+		FROM Transfer"#,
+	);
+	push_statement_filter(&mut builder, account, currency, filter);
+	builder
+		.push(" ORDER BY id DESC LIMIT ")
+		.push_bind(limit)
+		.push(" OFFSET ")
+		.push_bind(offset);
+
+	let ledger_rows = builder
+		.build_query_as::<LedgerRow>()
+		.fetch_all(pool)
+		.await?;
+
 	Ok(ledger_rows
 		.into_iter()
 		.filter_map(|row| Transfer::try_from((account, row)).ok())
 		.collect())
 }
 
+pub async fn count_statement(
+	pool: &SqlitePool,
+	account: i64,
+	currency: Currency,
+	filter: &StatementFilter,
+) -> anyhow::Result<i64> {
+	let mut builder = QueryBuilder::new("SELECT COUNT(*) FROM Transfer");
+	push_statement_filter(&mut builder, account, currency, filter);
+
+	Ok(builder.build_query_as::<(i64,)>().fetch_one(pool).await?.0)
+}
+
+pub async fn get_transfer(
+	pool: &SqlitePool,
+	guild: GuildId,
+	id: u32,
+) -> anyhow::Result<Option<LedgerRow>> {
+	Ok(sqlx::query_as::<_, LedgerRow>(
+		r#"SELECT *, (
+			SELECT reversal.id FROM Transfer AS reversal
+			WHERE reversal.reverses = Transfer.id
+		) AS reversed_by, (
+			SELECT Exchange.id FROM Exchange
+			WHERE Exchange.debit_id = Transfer.id OR Exchange.credit_id = Transfer.id
+		) AS exchange_id
+		FROM Transfer
+		WHERE guild_id=? AND id=?"#,
+	)
+	.bind(guild)
+	.bind(id)
+	.fetch_optional(pool)
+	.await?)
+}
+
 #[derive(Debug)]
 pub enum TransferStatus {
-	Authorized,
+	Authorized(i64),
 	InsuficientBalance,
 	BadValue,
+	/// The currency was retired before the transfer settled.
+	Retired,
 	Failed,
 }
 
-pub async fn send_transfer(
+async fn insert_transfer(
+	conn: &mut SqliteConnection,
 	from_account: i64,
 	to_account: i64,
 	currency: Currency,
 	value: i64,
-) -> TransferStatus {
+	description: Option<&str>,
+	reverses: Option<u32>,
+) -> anyhow::Result<i64> {
 	let currency_info = CurrencyInfo::from(currency);
-	let mut conn = match SqliteConnection::connect("sqlite://bank_database.db").await {
-		Ok(conn) => conn,
-		Err(_) => return TransferStatus::Failed,
-	};
 
-	let before_from_balance = match get_balance(from_account, currency).await {
-		Ok(balance) => balance,
-		Err(_) => return TransferStatus::Failed,
-	};
-	let before_to_balance = match get_balance(to_account, currency).await {
-		Ok(balance) => balance,
-		Err(_) => return TransferStatus::Failed,
-	};
-
-	if value <= 0 {
-		return TransferStatus::BadValue;
-	}
-
-	if value > before_from_balance && from_account != 0 {
-		return TransferStatus::InsuficientBalance;
-	}
+	let before_from_balance = fetch_balance(conn, from_account, currency).await?;
+	let before_to_balance = fetch_balance(conn, to_account, currency).await?;
 
 	let after_from_balance = before_from_balance - value;
 	let after_to_balance = before_to_balance + value;
@@ -124,55 +353,236 @@ pub async fn send_transfer(
 
 	let query = sqlx::query(
 		r#"INSERT INTO Transfer
-		(transfer_date, from_account, to_account, from_balance, to_balance, currency, value)
-		VALUES (?, ?, ?, ?, ?, ?, ?)"#,
+		(guild_id, transfer_date, from_account, to_account, from_balance, to_balance, currency, value, description, reverses)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
 	)
+	.bind(currency.guild())
 	.bind(timestamp_now)
 	.bind(from_account)
 	.bind(to_account)
 	.bind(after_from_balance)
 	.bind(after_to_balance)
 	.bind(currency_info.code)
-	.bind(value);
+	.bind(value)
+	.bind(description)
+	.bind(reverses);
+
+	let id = conn.execute(query).await?.last_insert_rowid();
+	chain::seal_row(conn, id).await?;
+	store_balance(conn, from_account, currency, after_from_balance).await?;
+	store_balance(conn, to_account, currency, after_to_balance).await?;
 
-	match conn.execute(query).await {
-		Ok(_) => TransferStatus::Authorized,
-		Err(_) => TransferStatus::Failed,
+	Ok(id)
+}
+
+/// Checks the sender's funds and writes the transfer. Must run inside an
+/// immediate transaction.
+pub async fn try_transfer(
+	conn: &mut SqliteConnection,
+	from_account: i64,
+	to_account: i64,
+	currency: Currency,
+	value: i64,
+	description: Option<&str>,
+) -> anyhow::Result<TransferStatus> {
+	if value <= 0 {
+		return Ok(TransferStatus::BadValue);
+	}
+
+	let before_from_balance = fetch_balance(conn, from_account, currency).await?;
+	if value > before_from_balance && from_account != BANK_ID {
+		return Ok(TransferStatus::InsuficientBalance);
+	}
+
+	let id = insert_transfer(
+		conn,
+		from_account,
+		to_account,
+		currency,
+		value,
+		description,
+		None,
+	)
+	.await?;
+	Ok(TransferStatus::Authorized(id))
+}
+
+pub async fn send_transfer(
+	pool: &SqlitePool,
+	from_account: i64,
+	to_account: i64,
+	currency: Currency,
+	value: i64,
+	description: Option<&str>,
+) -> TransferStatus {
+	if value <= 0 {
+		return TransferStatus::BadValue;
 	}
+
+	let mut conn = match pool.acquire().await {
+		Ok(conn) => conn,
+		Err(_) => return TransferStatus::Failed,
+	};
+
+	let description = description.map(str::to_owned);
+	let status = immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			try_transfer(
+				conn,
+				from_account,
+				to_account,
+				currency,
+				value,
+				description.as_deref(),
+			)
+			.await
+		})
+	})
+	.await;
+
+	status.unwrap_or(TransferStatus::Failed)
 }
 
 pub async fn force_transfer(
+	pool: &SqlitePool,
 	from_account: i64,
 	to_account: i64,
 	currency: Currency,
 	value: i64,
-) -> anyhow::Result<()> {
-	let currency_info = CurrencyInfo::from(currency);
-	let mut conn = SqliteConnection::connect("sqlite://bank_database.db").await?;
+	description: Option<&str>,
+) -> anyhow::Result<i64> {
+	let mut conn = pool.acquire().await?;
+
+	let description = description.map(str::to_owned);
+	immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			insert_transfer(
+				conn,
+				from_account,
+				to_account,
+				currency,
+				value,
+				description.as_deref(),
+				None,
+			)
+			.await
+		})
+	})
+	.await
+}
 
-	let before_from_balance = get_balance(from_account, currency).await?;
-	let before_to_balance = get_balance(to_account, currency).await?;
+#[derive(Debug)]
+pub enum ReversalStatus {
+	Reversed(i64),
+	NotFound,
+	AlreadyReversed,
+	IsReversal,
+	/// One leg of an exchange, which cannot be undone alone.
+	IsExchange,
+	InsuficientBalance,
+	Failed,
+}
 
-	let after_from_balance = before_from_balance - value;
-	let after_to_balance = before_to_balance + value;
-	let timestamp_now = get_current_time();
+/// Writes a compensating row that moves the value of transfer `id` back to
+/// its sender. Unless `force` is set, the recipient must still hold the funds.
+/// The original row is kept and linked from the compensating one. Legs of an
+/// exchange are refused, since reversing one would leave the other in place.
+pub async fn reverse_transfer(
+	pool: &SqlitePool,
+	guild: GuildId,
+	id: u32,
+	force: bool,
+	description: Option<&str>,
+) -> ReversalStatus {
+	let mut conn = match pool.acquire().await {
+		Ok(conn) => conn,
+		Err(_) => return ReversalStatus::Failed,
+	};
 
-	let query = sqlx::query(
-		r#"INSERT INTO Transfer
-		(transfer_date, from_account, to_account, from_balance, to_balance, currency, value)
-		VALUES (?, ?, ?, ?, ?, ?, ?)"#,
-	)
-	.bind(timestamp_now)
-	.bind(from_account)
-	.bind(to_account)
-	.bind(after_from_balance)
-	.bind(after_to_balance)
-	.bind(currency_info.code)
-	.bind(value);
+	let description = description.map(str::to_owned);
+	let status = immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			let original = sqlx::query_as::<_, LedgerRow>(
+				r#"SELECT *, (
+					SELECT reversal.id FROM Transfer AS reversal
+					WHERE reversal.reverses = Transfer.id
+				) AS reversed_by, (
+					SELECT Exchange.id FROM Exchange
+					WHERE Exchange.debit_id = Transfer.id OR Exchange.credit_id = Transfer.id
+				) AS exchange_id
+				FROM Transfer
+				WHERE guild_id=? AND id=?"#,
+			)
+			.bind(guild)
+			.bind(id)
+			.fetch_optional(&mut *conn)
+			.await?;
 
-	conn.execute(query).await?;
+			let original = match original {
+				Some(original) => original,
+				None => return Ok(ReversalStatus::NotFound),
+			};
 
-	Ok(())
+			if original.reversed_by.is_some() {
+				return Ok(ReversalStatus::AlreadyReversed);
+			}
+
+			if original.reverses.is_some() {
+				return Ok(ReversalStatus::IsReversal);
+			}
+
+			if original.exchange_id.is_some() {
+				return Ok(ReversalStatus::IsExchange);
+			}
+
+			let currency = Currency::try_from((original.guild_id, original.currency.as_str()))?;
+			let recipient_balance = fetch_balance(conn, original.to_account, currency).await?;
+			if !force
+				&& original.to_account != BANK_ID
+				&& original.value > 0
+				&& original.value > recipient_balance
+			{
+				return Ok(ReversalStatus::InsuficientBalance);
+			}
+
+			let reversal_id = insert_transfer(
+				conn,
+				original.to_account,
+				original.from_account,
+				currency,
+				original.value,
+				description.as_deref(),
+				Some(original.id),
+			)
+			.await?;
+
+			Ok(ReversalStatus::Reversed(reversal_id))
+		})
+	})
+	.await;
+
+	status.unwrap_or(ReversalStatus::Failed)
+}
+
+impl Transfer {
+	/// Classifies this operation from the point of view of `account`.
+	pub fn kind(&self, account: UserId) -> OperationKind {
+		if self.exchange_id.is_some() {
+			OperationKind::Exchange
+		} else if self.from_account == BANK_ID {
+			if self.value < 0 {
+				OperationKind::Charge
+			} else {
+				OperationKind::Deposit
+			}
+		} else if self.to_account == BANK_ID {
+			OperationKind::Charge
+		} else if self.from_account == account {
+			OperationKind::Sent
+		} else {
+			OperationKind::Received
+		}
+	}
 }
 
 impl TryFrom<(UserId, LedgerRow)> for Transfer {
@@ -180,7 +590,8 @@ impl TryFrom<(UserId, LedgerRow)> for Transfer {
 
 	fn try_from((id, row): (UserId, LedgerRow)) -> Result<Self, Self::Error> {
 		Ok(Self {
-			currency: Currency::try_from(row.currency.as_str())?,
+			id: row.id,
+			currency: Currency::try_from((row.guild_id, row.currency.as_str()))?,
 			from_account: row.from_account,
 			to_account: row.to_account,
 			balance: if row.from_account == id {
@@ -192,6 +603,10 @@ impl TryFrom<(UserId, LedgerRow)> for Transfer {
 			},
 			value: row.value,
 			date: row.transfer_date,
+			description: row.description,
+			reverses: row.reverses,
+			reversed_by: row.reversed_by,
+			exchange_id: row.exchange_id,
 		})
 	}
 }
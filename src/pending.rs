@@ -0,0 +1,202 @@
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+use std::env;
+use std::time::Duration;
+
+use crate::currency::{self, Currency};
+use crate::database::immediate_transaction;
+use crate::operation::{get_current_time, try_transfer, GuildId, TransferStatus, UserId};
+
+/// How long a transfer stays pending before it settles into the ledger.
+pub struct CancelWindow;
+
+impl TypeMapKey for CancelWindow {
+	type Value = Duration;
+}
+
+/// Reads `BANK_CANCEL_WINDOW` in seconds. Zero settles transfers immediately.
+pub fn cancel_window_from_env() -> anyhow::Result<Duration> {
+	Ok(match env::var("BANK_CANCEL_WINDOW") {
+		Ok(value) => Duration::from_secs(value.parse()?),
+		Err(_) => Duration::from_secs(30),
+	})
+}
+
+pub async fn get_cancel_window(ctx: &Context) -> Duration {
+	ctx.data
+		.read()
+		.await
+		.get::<CancelWindow>()
+		.copied()
+		.unwrap_or_default()
+}
+
+#[derive(Debug, sqlx::FromRow)]
+pub struct PendingTransfer {
+	pub id: u32,
+	pub guild_id: GuildId,
+	pub currency: String,
+	pub from_account: UserId,
+	pub to_account: UserId,
+	pub value: i64,
+	pub description: Option<String>,
+	pub channel_id: i64,
+	pub settle_date: i64,
+}
+
+#[derive(Debug)]
+pub enum CancelStatus {
+	Cancelled,
+	NotFound,
+	NotSender,
+	NotPending,
+}
+
+/// Stores `pending` and returns it with its new id.
+pub async fn create_pending(
+	pool: &SqlitePool,
+	mut pending: PendingTransfer,
+) -> anyhow::Result<PendingTransfer> {
+	let id = sqlx::query(
+		r#"INSERT INTO PendingTransfer
+		(guild_id, currency, from_account, to_account, value, description, channel_id, created_date, settle_date)
+		VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"#,
+	)
+	.bind(pending.guild_id)
+	.bind(&pending.currency)
+	.bind(pending.from_account)
+	.bind(pending.to_account)
+	.bind(pending.value)
+	.bind(&pending.description)
+	.bind(pending.channel_id)
+	.bind(get_current_time())
+	.bind(pending.settle_date)
+	.execute(pool)
+	.await?
+	.last_insert_rowid();
+
+	pending.id = id as u32;
+	Ok(pending)
+}
+
+/// Transfers that have not settled yet, oldest first.
+pub async fn get_unsettled(pool: &SqlitePool) -> anyhow::Result<Vec<PendingTransfer>> {
+	Ok(sqlx::query_as::<_, PendingTransfer>(
+		r#"SELECT * FROM PendingTransfer
+		WHERE status='pending'
+		ORDER BY settle_date"#,
+	)
+	.fetch_all(pool)
+	.await?)
+}
+
+/// Cancels pending transfer `id` on behalf of `account`, which must be its
+/// sender.
+pub async fn cancel_pending(
+	pool: &SqlitePool,
+	id: u32,
+	account: UserId,
+) -> anyhow::Result<CancelStatus> {
+	let mut conn = pool.acquire().await?;
+
+	immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			let row = sqlx::query_as::<_, (UserId, String)>(
+				"SELECT from_account, status FROM PendingTransfer WHERE id=?",
+			)
+			.bind(id)
+			.fetch_optional(&mut *conn)
+			.await?;
+
+			let (from_account, status) = match row {
+				Some(row) => row,
+				None => return Ok(CancelStatus::NotFound),
+			};
+
+			if from_account != account {
+				return Ok(CancelStatus::NotSender);
+			}
+
+			if status != "pending" {
+				return Ok(CancelStatus::NotPending);
+			}
+
+			sqlx::query("UPDATE PendingTransfer SET status='cancelled' WHERE id=?")
+				.bind(id)
+				.execute(conn)
+				.await?;
+
+			Ok(CancelStatus::Cancelled)
+		})
+	})
+	.await
+}
+
+/// Moves pending transfer `id` into the ledger. Returns `None` when it was
+/// cancelled or already settled, so settling twice is harmless. A transfer
+/// whose currency no longer exists is marked failed.
+pub async fn settle_pending(pool: &SqlitePool, id: u32) -> anyhow::Result<Option<TransferStatus>> {
+	let mut conn = pool.acquire().await?;
+
+	immediate_transaction(&mut conn, |conn| {
+		Box::pin(async move {
+			let pending = sqlx::query_as::<_, PendingTransfer>(
+				"SELECT * FROM PendingTransfer WHERE id=? AND status='pending'",
+			)
+			.bind(id)
+			.fetch_optional(&mut *conn)
+			.await?;
+
+			let pending = match pending {
+				Some(pending) => pending,
+				None => return Ok(None),
+			};
+
+			// A currency removed while the transfer waited can never settle.
+			let currency = match Currency::try_from((pending.guild_id, pending.currency.as_str())) {
+				Ok(currency) => currency,
+				Err(_) => {
+					sqlx::query("UPDATE PendingTransfer SET status='failed' WHERE id=?")
+						.bind(id)
+						.execute(conn)
+						.await?;
+					return Ok(Some(TransferStatus::Failed));
+				}
+			};
+
+			// Retiring a currency freezes the transfers still waiting in it.
+			if currency::is_retired(conn, currency).await? {
+				sqlx::query("UPDATE PendingTransfer SET status='failed' WHERE id=?")
+					.bind(id)
+					.execute(conn)
+					.await?;
+				return Ok(Some(TransferStatus::Retired));
+			}
+
+			let status = try_transfer(
+				conn,
+				pending.from_account,
+				pending.to_account,
+				currency,
+				pending.value,
+				pending.description.as_deref(),
+			)
+			.await?;
+
+			let (state, transfer_id) = match status {
+				TransferStatus::Authorized(transfer_id) => ("settled", Some(transfer_id)),
+				_ => ("failed", None),
+			};
+
+			sqlx::query("UPDATE PendingTransfer SET status=?, transfer_id=? WHERE id=?")
+				.bind(state)
+				.bind(transfer_id)
+				.bind(id)
+				.execute(conn)
+				.await?;
+
+			Ok(Some(status))
+		})
+	})
+	.await
+}
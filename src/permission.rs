@@ -0,0 +1,300 @@
+use serenity::model::id::RoleId;
+use serenity::model::user::User;
+use serenity::prelude::*;
+use sqlx::SqlitePool;
+
+use crate::operation::{get_current_time, GuildId, UserId};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+	Director,
+	Auditor,
+	Treasurer,
+	Teller,
+}
+
+pub const ROLES: [Role; 4] = [Role::Director, Role::Auditor, Role::Treasurer, Role::Teller];
+
+impl Role {
+	pub fn name(self) -> &'static str {
+		match self {
+			Role::Director => "director",
+			Role::Auditor => "auditor",
+			Role::Treasurer => "treasurer",
+			Role::Teller => "teller",
+		}
+	}
+
+	pub fn permissions(self) -> &'static [Permission] {
+		match self {
+			Role::Director => &PERMISSIONS,
+			Role::Auditor => &[
+				Permission::Audit,
+				Permission::PublishHead,
+				Permission::ViewReceipts,
+				Permission::ViewAuditLog,
+			],
+			Role::Treasurer => &[
+				Permission::CreateMoney,
+				Permission::ManageRates,
+				Permission::ManageCurrencies,
+			],
+			Role::Teller => &[Permission::Reverse, Permission::ViewReceipts],
+		}
+	}
+}
+
+impl TryFrom<&str> for Role {
+	type Error = anyhow::Error;
+
+	fn try_from(value: &str) -> Result<Self, Self::Error> {
+		ROLES
+			.into_iter()
+			.find(|role| role.name().eq_ignore_ascii_case(value))
+			.ok_or_else(|| anyhow::anyhow!("Unknown role {}.", value))
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Permission {
+	/// Deposit and charge money with `!create`.
+	CreateMoney,
+	Reverse,
+	Rebuild,
+	Audit,
+	PublishHead,
+	ManageRates,
+	ManageCurrencies,
+	/// Read the receipt of any operation, not only one's own.
+	ViewReceipts,
+	ManageRoles,
+	ViewAuditLog,
+	/// Change how the bank behaves in the guild, such as its prefix and channels.
+	ManageGuild,
+}
+
+pub const PERMISSIONS: [Permission; 11] = [
+	Permission::CreateMoney,
+	Permission::Reverse,
+	Permission::Rebuild,
+	Permission::Audit,
+	Permission::PublishHead,
+	Permission::ManageRates,
+	Permission::ManageCurrencies,
+	Permission::ViewReceipts,
+	Permission::ManageRoles,
+	Permission::ViewAuditLog,
+	Permission::ManageGuild,
+];
+
+impl Permission {
+	pub fn name(self) -> &'static str {
+		match self {
+			Permission::CreateMoney => "create money",
+			Permission::Reverse => "reverse operations",
+			Permission::Rebuild => "rebuild balances",
+			Permission::Audit => "audit",
+			Permission::PublishHead => "publish the ledger head",
+			Permission::ManageRates => "manage exchange rates",
+			Permission::ManageCurrencies => "manage currencies",
+			Permission::ViewReceipts => "view any receipt",
+			Permission::ManageRoles => "manage roles",
+			Permission::ViewAuditLog => "view the admin log",
+			Permission::ManageGuild => "configure the bank",
+		}
+	}
+}
+
+/// What the author of a message may do in one guild.
+pub struct Access {
+	/// The bot operator from `DIRECTOR_ID`, allowed everything everywhere.
+	pub operator: bool,
+	pub roles: Vec<Role>,
+}
+
+impl Access {
+	pub fn allows(&self, permission: Permission) -> bool {
+		self.operator
+			|| self
+				.roles
+				.iter()
+				.any(|role| role.permissions().contains(&permission))
+	}
+}
+
+/// Finds the roles of `user` in `guild`, both granted directly and through
+/// their Discord roles there. `discord_roles` comes with messages and
+/// interactions sent in the guild; without it they are looked up.
+pub async fn get_access(
+	ctx: &Context,
+	pool: &SqlitePool,
+	user: &User,
+	discord_roles: Option<&[RoleId]>,
+	guild: GuildId,
+	operator_id: u64,
+) -> anyhow::Result<Access> {
+	let discord_roles: Vec<i64> = match discord_roles {
+		Some(roles) => roles.iter().map(|role| *role.as_u64() as i64).collect(),
+		None => match ctx.http.get_member(guild as u64, *user.id.as_u64()).await {
+			Ok(member) => member
+				.roles
+				.iter()
+				.map(|role| *role.as_u64() as i64)
+				.collect(),
+			Err(_) => Vec::new(),
+		},
+	};
+
+	let mut roles = get_user_roles(pool, guild, *user.id.as_u64() as UserId).await?;
+	for (discord_role, role) in get_role_mappings(pool, guild).await? {
+		if discord_roles.contains(&discord_role) && !roles.contains(&role) {
+			roles.push(role);
+		}
+	}
+
+	Ok(Access {
+		operator: *user.id.as_u64() == operator_id,
+		roles,
+	})
+}
+
+pub async fn get_user_roles(
+	pool: &SqlitePool,
+	guild: GuildId,
+	user: UserId,
+) -> anyhow::Result<Vec<Role>> {
+	Ok(sqlx::query_as::<_, (String,)>(
+		r#"SELECT role FROM RoleGrant
+		WHERE guild_id=? AND user_id=?"#,
+	)
+	.bind(guild)
+	.bind(user)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.filter_map(|row| Role::try_from(row.0.as_str()).ok())
+	.collect())
+}
+
+/// Every direct grant in `guild`, as user and role.
+pub async fn get_role_grants(
+	pool: &SqlitePool,
+	guild: GuildId,
+) -> anyhow::Result<Vec<(UserId, Role)>> {
+	Ok(sqlx::query_as::<_, (UserId, String)>(
+		r#"SELECT user_id, role FROM RoleGrant
+		WHERE guild_id=?
+		ORDER BY granted_date"#,
+	)
+	.bind(guild)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.filter_map(|(user, role)| {
+		let role = Role::try_from(role.as_str()).ok()?;
+		Some((user, role))
+	})
+	.collect())
+}
+
+/// Every Discord role of `guild` mapped to a bank role.
+pub async fn get_role_mappings(
+	pool: &SqlitePool,
+	guild: GuildId,
+) -> anyhow::Result<Vec<(i64, Role)>> {
+	Ok(sqlx::query_as::<_, (i64, String)>(
+		r#"SELECT discord_role_id, role FROM RoleMapping
+		WHERE guild_id=?
+		ORDER BY granted_date"#,
+	)
+	.bind(guild)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.filter_map(|(discord_role, role)| {
+		let role = Role::try_from(role.as_str()).ok()?;
+		Some((discord_role, role))
+	})
+	.collect())
+}
+
+/// Returns false when the user already had the role.
+pub async fn grant_role(
+	pool: &SqlitePool,
+	guild: GuildId,
+	user: UserId,
+	role: Role,
+) -> anyhow::Result<bool> {
+	let inserted = sqlx::query(
+		r#"INSERT OR IGNORE INTO RoleGrant (guild_id, user_id, role, granted_date)
+		VALUES (?, ?, ?, ?)"#,
+	)
+	.bind(guild)
+	.bind(user)
+	.bind(role.name())
+	.bind(get_current_time())
+	.execute(pool)
+	.await?
+	.rows_affected();
+
+	Ok(inserted > 0)
+}
+
+/// Returns false when the user did not have the role.
+pub async fn revoke_role(
+	pool: &SqlitePool,
+	guild: GuildId,
+	user: UserId,
+	role: Role,
+) -> anyhow::Result<bool> {
+	let deleted = sqlx::query("DELETE FROM RoleGrant WHERE guild_id=? AND user_id=? AND role=?")
+		.bind(guild)
+		.bind(user)
+		.bind(role.name())
+		.execute(pool)
+		.await?
+		.rows_affected();
+
+	Ok(deleted > 0)
+}
+
+/// Returns false when the Discord role was already mapped to the role.
+pub async fn map_role(
+	pool: &SqlitePool,
+	guild: GuildId,
+	discord_role: i64,
+	role: Role,
+) -> anyhow::Result<bool> {
+	let inserted = sqlx::query(
+		r#"INSERT OR IGNORE INTO RoleMapping (guild_id, discord_role_id, role, granted_date)
+		VALUES (?, ?, ?, ?)"#,
+	)
+	.bind(guild)
+	.bind(discord_role)
+	.bind(role.name())
+	.bind(get_current_time())
+	.execute(pool)
+	.await?
+	.rows_affected();
+
+	Ok(inserted > 0)
+}
+
+/// Returns false when the Discord role was not mapped to the role.
+pub async fn unmap_role(
+	pool: &SqlitePool,
+	guild: GuildId,
+	discord_role: i64,
+	role: Role,
+) -> anyhow::Result<bool> {
+	let deleted =
+		sqlx::query("DELETE FROM RoleMapping WHERE guild_id=? AND discord_role_id=? AND role=?")
+			.bind(guild)
+			.bind(discord_role)
+			.bind(role.name())
+			.execute(pool)
+			.await?
+			.rows_affected();
+
+	Ok(deleted > 0)
+}
@@ -0,0 +1,364 @@
+use crate::permission::Permission;
+
+/// What a command does, matched on by the handler to run it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+	Ping,
+	Help,
+	Timezone,
+	Confirm,
+	Guild,
+	Balance,
+	Transfer,
+	Cancel,
+	Statement,
+	Stat,
+	Receipt,
+	Request,
+	Invoices,
+	Rates,
+	Exchange,
+	Roles,
+	Create,
+	Reverse,
+	Rebuild,
+	Audit,
+	PublishHead,
+	Rate,
+	Currency,
+	Grant,
+	Revoke,
+	AuditLog,
+	LogChannel,
+	Config,
+}
+
+pub struct Command {
+	pub name: &'static str,
+	pub aliases: &'static [&'static str],
+	/// Argument forms shown in help, each written after the command name. Only
+	/// the name is matched when dispatching; each command parses its own arguments.
+	pub usage: &'static [&'static str],
+	pub help: &'static str,
+	pub permission: Option<Permission>,
+	pub action: Action,
+}
+
+impl Command {
+	/// Every accepted form, such as "`!cancel <id>`".
+	pub fn format_usage(&self, prefix: &str) -> String {
+		self.usage
+			.iter()
+			.map(|form| {
+				if form.is_empty() {
+					format!("`{}{}`", prefix, self.name)
+				} else {
+					format!("`{}{} {}`", prefix, self.name, form)
+				}
+			})
+			.collect::<Vec<_>>()
+			.join(" or ")
+	}
+}
+
+pub const COMMANDS: &[Command] = &[
+	Command {
+		name: "help",
+		aliases: &[],
+		usage: &["", "<command>"],
+		help: "Lists the commands, or explains one of them.",
+		permission: None,
+		action: Action::Help,
+	},
+	Command {
+		name: "ping",
+		aliases: &[],
+		usage: &[""],
+		help: "Checks that the bot is online.",
+		permission: None,
+		action: Action::Ping,
+	},
+	Command {
+		name: "balance",
+		aliases: &["bal"],
+		usage: &["[currency...]"],
+		help: "Shows your balance in every currency you hold, or in the given ones.",
+		permission: None,
+		action: Action::Balance,
+	},
+	Command {
+		name: "transfer",
+		aliases: &["send"],
+		usage: &["@user <amount> <currency> [memo]"],
+		help: "Sends money to someone. Large transfers ask for confirmation and transfers can be cancelled for a short while.",
+		permission: None,
+		action: Action::Transfer,
+	},
+	Command {
+		name: "cancel",
+		aliases: &[],
+		usage: &["<id>"],
+		help: "Cancels one of your pending transfers before it settles.",
+		permission: None,
+		action: Action::Cancel,
+	},
+	Command {
+		name: "statement",
+		aliases: &["history"],
+		usage: &["<currency> [deposits|charges|sent|received|transfers|exchanges] [@user] [since YYYY-MM-DD] [until YYYY-MM-DD] [page]"],
+		help: "Lists your operations in a currency, page by page.",
+		permission: None,
+		action: Action::Statement,
+	},
+	Command {
+		name: "stat",
+		aliases: &[],
+		usage: &["<currency>"],
+		help: "Shows the money supply, GDP and inequality of a currency.",
+		permission: None,
+		action: Action::Stat,
+	},
+	Command {
+		name: "receipt",
+		aliases: &[],
+		usage: &["<transfer id>"],
+		help: "Shows the full detail of one of your operations.",
+		permission: None,
+		action: Action::Receipt,
+	},
+	Command {
+		name: "request",
+		aliases: &[],
+		usage: &["@user <amount> <currency> [until YYYY-MM-DD] [memo]"],
+		help: "Asks someone to pay you. They get Pay and Decline buttons.",
+		permission: None,
+		action: Action::Request,
+	},
+	Command {
+		name: "invoices",
+		aliases: &[],
+		usage: &[""],
+		help: "Lists the open payment requests you sent or received.",
+		permission: None,
+		action: Action::Invoices,
+	},
+	Command {
+		name: "exchange",
+		aliases: &[],
+		usage: &["[amount] <from> <to>"],
+		help: "Converts money between currencies at the current rate after showing a quote, for example `!exchange 100 USD EUR`. The amount defaults to one unit.",
+		permission: None,
+		action: Action::Exchange,
+	},
+	Command {
+		name: "rates",
+		aliases: &[],
+		usage: &["<currency>", "<from> <to> [days]d"],
+		help: "Shows the history of an exchange rate, for example `!rates USD EUR 30d`.",
+		permission: None,
+		action: Action::Rates,
+	},
+	Command {
+		name: "timezone",
+		aliases: &[],
+		usage: &["", "<timezone>"],
+		help: "Shows or sets the timezone of your dates, such as `America/Sao_Paulo`.",
+		permission: None,
+		action: Action::Timezone,
+	},
+	Command {
+		name: "confirm",
+		aliases: &[],
+		usage: &["", "<whole units>", "off"],
+		help: "Shows or sets the amount from which your transfers ask for confirmation.",
+		permission: None,
+		action: Action::Confirm,
+	},
+	Command {
+		name: "guild",
+		aliases: &[],
+		usage: &["", "<number>"],
+		help: "In direct messages, picks the server whose ledger your commands use.",
+		permission: None,
+		action: Action::Guild,
+	},
+	Command {
+		name: "roles",
+		aliases: &[],
+		usage: &[""],
+		help: "Lists who holds each bank role.",
+		permission: None,
+		action: Action::Roles,
+	},
+	Command {
+		name: "create",
+		aliases: &[],
+		usage: &["[@user] <amount> <currency> [memo]"],
+		help: "Deposits money into an account, yours when nobody is mentioned. Negative amounts are charges.",
+		permission: Some(Permission::CreateMoney),
+		action: Action::Create,
+	},
+	Command {
+		name: "reverse",
+		aliases: &[],
+		usage: &["<transfer id> [force] [memo]"],
+		help: "Reverses an operation. `force` reverses it even when the receiver no longer holds the funds.",
+		permission: Some(Permission::Reverse),
+		action: Action::Reverse,
+	},
+	Command {
+		name: "rebuild",
+		aliases: &[],
+		usage: &[""],
+		help: "Recomputes every balance from the ledger.",
+		permission: Some(Permission::Rebuild),
+		action: Action::Rebuild,
+	},
+	Command {
+		name: "audit",
+		aliases: &[],
+		usage: &[""],
+		help: "Checks the hash chain, the balances and the money supply of the ledger.",
+		permission: Some(Permission::Audit),
+		action: Action::Audit,
+	},
+	Command {
+		name: "publishhead",
+		aliases: &[],
+		usage: &["[#channel]"],
+		help: "Posts the hash of the latest operation, so the ledger can be checked later.",
+		permission: Some(Permission::PublishHead),
+		action: Action::PublishHead,
+	},
+	Command {
+		name: "rate",
+		aliases: &[],
+		usage: &["", "refresh", "<code> <rate>", "<code> off"],
+		help: "Lists, refreshes, sets or removes the exchange rates.",
+		permission: Some(Permission::ManageRates),
+		action: Action::Rate,
+	},
+	Command {
+		name: "currency",
+		aliases: &[],
+		usage: &[
+			"list",
+			"create <code> <subunitexp> <prefix> <posfix> <picture> <name>",
+			"edit <code> <field> <value>",
+			"retire <code>",
+			"restore <code>",
+		],
+		help: "Manages the currencies of the ledger.",
+		permission: Some(Permission::ManageCurrencies),
+		action: Action::Currency,
+	},
+	Command {
+		name: "grant",
+		aliases: &[],
+		usage: &["<role> @user", "<role> @role"],
+		help: "Gives a bank role to users or to everyone with a Discord role.",
+		permission: Some(Permission::ManageRoles),
+		action: Action::Grant,
+	},
+	Command {
+		name: "revoke",
+		aliases: &[],
+		usage: &["<role> @user", "<role> @role"],
+		help: "Takes a bank role back.",
+		permission: Some(Permission::ManageRoles),
+		action: Action::Revoke,
+	},
+	Command {
+		name: "auditlog",
+		aliases: &[],
+		usage: &["[page]"],
+		help: "Pages through the privileged commands that were run or denied.",
+		permission: Some(Permission::ViewAuditLog),
+		action: Action::AuditLog,
+	},
+	Command {
+		name: "logchannel",
+		aliases: &[],
+		usage: &["", "#channel", "off"],
+		help: "Shows or sets the channel receiving live copies of the admin log.",
+		permission: Some(Permission::ManageGuild),
+		action: Action::LogChannel,
+	},
+	Command {
+		name: "config",
+		aliases: &[],
+		usage: &[
+			"",
+			"prefix <prefix>",
+			"channels add #channel...",
+			"channels remove #channel...",
+			"channels clear",
+			"announcements #channel",
+			"announcements off",
+		],
+		help: "Shows or changes the command prefix, the channels where bank commands are allowed and the announcement channel.",
+		permission: Some(Permission::ManageGuild),
+		action: Action::Config,
+	},
+];
+
+/// Finds a command by its exact name or alias, ignoring case.
+pub fn find(name: &str) -> Option<&'static Command> {
+	COMMANDS.iter().find(|command| {
+		command.name.eq_ignore_ascii_case(name)
+			|| command
+				.aliases
+				.iter()
+				.any(|alias| alias.eq_ignore_ascii_case(name))
+	})
+}
+
+/// Finds the command a message invokes with `prefix`, if it is one.
+pub fn parse(content: &str, prefix: &str) -> Option<&'static Command> {
+	let name = content.split_whitespace().next()?.strip_prefix(prefix)?;
+	find(name)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn action(content: &str, prefix: &str) -> Option<Action> {
+		parse(content, prefix).map(|command| command.action)
+	}
+
+	#[test]
+	fn parse_matches_whole_names_only() {
+		assert_eq!(action("!balancefoo", "!"), None);
+		assert_eq!(action("!statementx USD", "!"), None);
+		assert_eq!(action("!bal2", "!"), None);
+		assert_eq!(action("!", "!"), None);
+		assert_eq!(action("", "!"), None);
+	}
+
+	#[test]
+	fn parse_resolves_aliases_and_case() {
+		assert_eq!(action("!bal", "!"), Some(Action::Balance));
+		assert_eq!(action("!STAT USD", "!"), Some(Action::Stat));
+		assert_eq!(action("!Send @user 5 USD", "!"), Some(Action::Transfer));
+		assert_eq!(action("  !statement USD", "!"), Some(Action::Statement));
+	}
+
+	#[test]
+	fn parse_requires_the_prefix() {
+		assert_eq!(action("balance", "!"), None);
+		assert_eq!(action("!balance", "$"), None);
+		assert_eq!(action("$balance", "$"), Some(Action::Balance));
+		assert_eq!(action("bank.bal", "bank."), Some(Action::Balance));
+	}
+
+	#[test]
+	fn find_ignores_prefixes_and_partial_names() {
+		assert_eq!(
+			find("history").map(|command| command.action),
+			Some(Action::Statement)
+		);
+		assert!(find("!balance").is_none());
+		assert!(find("balanc").is_none());
+	}
+}
@@ -0,0 +1,99 @@
+use chrono_tz::Tz;
+use sqlx::SqlitePool;
+
+use crate::operation::{GuildId, UserId};
+
+/// Timezone used for users who never picked one with `!timezone`.
+pub const DEFAULT_TIMEZONE: Tz = Tz::UTC;
+
+pub async fn get_timezone(pool: &SqlitePool, account: UserId) -> anyhow::Result<Tz> {
+	let timezone = sqlx::query_as::<_, (Option<String>,)>(
+		r#"SELECT timezone FROM UserSettings
+		WHERE account=?"#,
+	)
+	.bind(account)
+	.fetch_optional(pool)
+	.await?
+	.and_then(|row| row.0);
+
+	Ok(timezone
+		.and_then(|name| name.parse::<Tz>().ok())
+		.unwrap_or(DEFAULT_TIMEZONE))
+}
+
+pub async fn set_timezone(pool: &SqlitePool, account: UserId, timezone: Tz) -> anyhow::Result<()> {
+	sqlx::query(
+		r#"INSERT INTO UserSettings (account, timezone)
+		VALUES (?, ?)
+		ON CONFLICT(account) DO UPDATE SET timezone=excluded.timezone"#,
+	)
+	.bind(account)
+	.bind(timezone.name())
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+/// Transfers of at least this many whole units ask for confirmation first.
+pub async fn get_confirm_threshold(
+	pool: &SqlitePool,
+	account: UserId,
+) -> anyhow::Result<Option<i64>> {
+	Ok(sqlx::query_as::<_, (Option<i64>,)>(
+		r#"SELECT confirm_threshold FROM UserSettings
+		WHERE account=?"#,
+	)
+	.bind(account)
+	.fetch_optional(pool)
+	.await?
+	.and_then(|row| row.0))
+}
+
+pub async fn set_confirm_threshold(
+	pool: &SqlitePool,
+	account: UserId,
+	threshold: Option<i64>,
+) -> anyhow::Result<()> {
+	sqlx::query(
+		r#"INSERT INTO UserSettings (account, confirm_threshold)
+		VALUES (?, ?)
+		ON CONFLICT(account) DO UPDATE SET confirm_threshold=excluded.confirm_threshold"#,
+	)
+	.bind(account)
+	.bind(threshold)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
+
+/// Guild that `account`'s direct messages act on, picked with `!guild`.
+pub async fn get_dm_guild(pool: &SqlitePool, account: UserId) -> anyhow::Result<Option<GuildId>> {
+	Ok(sqlx::query_as::<_, (Option<GuildId>,)>(
+		r#"SELECT dm_guild FROM UserSettings
+		WHERE account=?"#,
+	)
+	.bind(account)
+	.fetch_optional(pool)
+	.await?
+	.and_then(|row| row.0))
+}
+
+pub async fn set_dm_guild(
+	pool: &SqlitePool,
+	account: UserId,
+	guild: GuildId,
+) -> anyhow::Result<()> {
+	sqlx::query(
+		r#"INSERT INTO UserSettings (account, dm_guild)
+		VALUES (?, ?)
+		ON CONFLICT(account) DO UPDATE SET dm_guild=excluded.dm_guild"#,
+	)
+	.bind(account)
+	.bind(guild)
+	.execute(pool)
+	.await?;
+
+	Ok(())
+}
@@ -0,0 +1,345 @@
+use serenity::builder::CreateApplicationCommandOption;
+use serenity::model::interactions::application_command::{
+	ApplicationCommandInteraction, ApplicationCommandInteractionDataOptionValue,
+	ApplicationCommandOptionType,
+};
+use serenity::model::user::User;
+use serenity::prelude::*;
+
+use crate::adminlog::Outcome;
+use crate::commands::{self, Invocation};
+use crate::currency::{self, Currency, CurrencyInfo};
+use crate::database;
+use crate::operation::{GuildId, OperationKind, StatementFilter, MAX_DESCRIPTION_LENGTH};
+use crate::settings;
+
+/// A currency option offering the active currencies of `guild` as choices.
+fn currency_option(guild: GuildId, required: bool) -> CreateApplicationCommandOption {
+	let mut option = CreateApplicationCommandOption::default();
+	option
+		.name("currency")
+		.description("Currency")
+		.kind(ApplicationCommandOptionType::String)
+		.required(required);
+
+	// Discord accepts at most 25 choices.
+	for currency in currency::active_currencies(guild).into_iter().take(25) {
+		let info = CurrencyInfo::from(currency);
+		option.add_string_choice(format!("{} ({})", info.name, info.code), currency.code());
+	}
+
+	option
+}
+
+/// Registers the slash commands of `guild`. The currency choices come from the
+/// guild, so this runs again whenever its currencies change.
+pub async fn register_commands(ctx: &Context, guild: GuildId) -> anyhow::Result<()> {
+	serenity::model::id::GuildId(guild as u64)
+		.set_application_commands(&ctx.http, |commands| {
+			commands
+				.create_application_command(|c| {
+					c.name("balance")
+						.description("Show your balance")
+						.add_option(currency_option(guild, false))
+				})
+				.create_application_command(|c| {
+					c.name("transfer")
+						.description("Transfer money to someone")
+						.create_option(|o| {
+							o.name("user")
+								.description("Who receives the money")
+								.kind(ApplicationCommandOptionType::User)
+								.required(true)
+						})
+						.add_option(currency_option(guild, true))
+						.create_option(|o| {
+							o.name("amount")
+								.description("Amount to transfer")
+								.kind(ApplicationCommandOptionType::Number)
+								.min_number_value(0.0)
+								.required(true)
+						})
+						.create_option(|o| {
+							o.name("memo")
+								.description("Description of the transfer")
+								.kind(ApplicationCommandOptionType::String)
+						})
+				})
+				.create_application_command(|c| {
+					c.name("statement")
+						.description("Show your statement")
+						.add_option(currency_option(guild, true))
+						.create_option(|o| {
+							o.name("kind")
+								.description("Only show one kind of operation")
+								.kind(ApplicationCommandOptionType::String)
+								.add_string_choice("Deposits", "deposits")
+								.add_string_choice("Charges", "charges")
+								.add_string_choice("Sent", "sent")
+								.add_string_choice("Received", "received")
+								.add_string_choice("Transfers", "transfers")
+								.add_string_choice("Exchanges", "exchanges")
+						})
+						.create_option(|o| {
+							o.name("user")
+								.description("Only show operations with this user")
+								.kind(ApplicationCommandOptionType::User)
+						})
+						.create_option(|o| {
+							o.name("since")
+								.description("First day, as YYYY-MM-DD")
+								.kind(ApplicationCommandOptionType::String)
+						})
+						.create_option(|o| {
+							o.name("until")
+								.description("Last day, as YYYY-MM-DD")
+								.kind(ApplicationCommandOptionType::String)
+						})
+						.create_option(|o| {
+							o.name("page")
+								.description("Page to start from")
+								.kind(ApplicationCommandOptionType::Integer)
+								.min_int_value(1)
+						})
+				})
+				.create_application_command(|c| {
+					c.name("stat")
+						.description("Show the statistics of a currency")
+						.add_option(currency_option(guild, true))
+				})
+				.create_application_command(|c| {
+					c.name("create")
+						.description(
+							"Deposit money into an account, or charge it with a negative amount",
+						)
+						.add_option(currency_option(guild, true))
+						.create_option(|o| {
+							o.name("amount")
+								.description("Amount to deposit")
+								.kind(ApplicationCommandOptionType::Number)
+								.required(true)
+						})
+						.create_option(|o| {
+							o.name("user")
+								.description("Whose account, yours when not given")
+								.kind(ApplicationCommandOptionType::User)
+						})
+						.create_option(|o| {
+							o.name("memo")
+								.description("Description of the operation")
+								.kind(ApplicationCommandOptionType::String)
+						})
+				})
+		})
+		.await?;
+
+	Ok(())
+}
+
+fn option<'a>(
+	interaction: &'a ApplicationCommandInteraction,
+	name: &str,
+) -> Option<&'a ApplicationCommandInteractionDataOptionValue> {
+	interaction
+		.data
+		.options
+		.iter()
+		.find(|option| option.name == name)?
+		.resolved
+		.as_ref()
+}
+
+fn string_option<'a>(
+	interaction: &'a ApplicationCommandInteraction,
+	name: &str,
+) -> Option<&'a str> {
+	match option(interaction, name)? {
+		ApplicationCommandInteractionDataOptionValue::String(value) => Some(value),
+		_ => None,
+	}
+}
+
+fn user_option<'a>(interaction: &'a ApplicationCommandInteraction, name: &str) -> Option<&'a User> {
+	match option(interaction, name)? {
+		ApplicationCommandInteractionDataOptionValue::User(user, _) => Some(user),
+		_ => None,
+	}
+}
+
+fn integer_option(interaction: &ApplicationCommandInteraction, name: &str) -> Option<i64> {
+	match option(interaction, name)? {
+		ApplicationCommandInteractionDataOptionValue::Integer(value) => Some(*value),
+		_ => None,
+	}
+}
+
+fn number_option(interaction: &ApplicationCommandInteraction, name: &str) -> Option<f64> {
+	match option(interaction, name)? {
+		ApplicationCommandInteractionDataOptionValue::Number(value) => Some(*value),
+		_ => None,
+	}
+}
+
+fn memo_option(interaction: &ApplicationCommandInteraction) -> Option<String> {
+	string_option(interaction, "memo")
+		.map(|memo| memo.trim().replace('`', "'"))
+		.filter(|memo| !memo.is_empty())
+		.map(|memo| memo.chars().take(MAX_DESCRIPTION_LENGTH).collect())
+}
+
+/// Finds the currency and the amount in minor units, or the reply explaining
+/// what is wrong with them.
+fn payment_options(
+	interaction: &ApplicationCommandInteraction,
+	guild: GuildId,
+) -> Result<(Currency, i64), String> {
+	let currency = string_option(interaction, "currency")
+		.and_then(|code| Currency::try_from((guild, code)).ok())
+		.ok_or("Please specify a currency.")?;
+
+	let amount = number_option(interaction, "amount").ok_or("Please specify an amount.")?;
+	let value = currency::amount_from_number(amount, currency).map_err(|err| err.to_string())?;
+
+	Ok((currency, value))
+}
+
+/// The command as a line of text, for the admin log.
+pub fn describe(interaction: &ApplicationCommandInteraction) -> String {
+	let mut text = format!("/{}", interaction.data.name);
+	for option in &interaction.data.options {
+		let value = match &option.resolved {
+			Some(ApplicationCommandInteractionDataOptionValue::User(user, _)) => {
+				format!("<@{}>", user.id.as_u64())
+			}
+			Some(ApplicationCommandInteractionDataOptionValue::String(value)) => value.clone(),
+			_ => option
+				.value
+				.as_ref()
+				.map(|value| value.to_string())
+				.unwrap_or_default(),
+		};
+		text.push_str(&format!(" {}:{}", option.name, value));
+	}
+
+	text
+}
+
+/// Runs a slash command through the same logic as its prefix command. The
+/// interaction must have been deferred, and `inv` is the one wrapping it, so
+/// replies made before an error are not overwritten. Returns the outcome for
+/// the admin log.
+pub async fn execute(
+	ctx: &Context,
+	inv: &Invocation<'_>,
+	interaction: &ApplicationCommandInteraction,
+	guild: GuildId,
+) -> anyhow::Result<Outcome> {
+	let result = match interaction.data.name.as_str() {
+		"balance" => {
+			let currencies = string_option(interaction, "currency")
+				.map(|code| Currency::try_from((guild, code)).into_iter().collect());
+			commands::show_balance(ctx, inv, guild, currencies).await
+		}
+		"transfer" => {
+			let to = match user_option(interaction, "user") {
+				Some(user) => user,
+				None => {
+					inv.say(ctx, "Please pick the individual you want to transfer.")
+						.await;
+					return Ok(Outcome::Failed);
+				}
+			};
+
+			match payment_options(interaction, guild) {
+				Ok((currency, value)) => {
+					let memo = memo_option(interaction);
+					commands::make_transfer(ctx, inv, to, currency, value, memo).await
+				}
+				Err(response) => {
+					inv.say(ctx, &response).await;
+					Ok(())
+				}
+			}
+		}
+		"statement" => {
+			let currency = match string_option(interaction, "currency")
+				.and_then(|code| Currency::try_from((guild, code)).ok())
+			{
+				Some(currency) => currency,
+				None => {
+					inv.say(ctx, "Please specify a currency.").await;
+					return Ok(Outcome::Failed);
+				}
+			};
+
+			let pool = database::get_pool(ctx).await;
+			let account = *interaction.user.id.as_u64() as i64;
+			let timezone = settings::get_timezone(&pool, account).await?;
+
+			let mut filter = StatementFilter::default();
+			match string_option(interaction, "kind") {
+				Some("deposits") => filter.kinds.push(OperationKind::Deposit),
+				Some("charges") => filter.kinds.push(OperationKind::Charge),
+				Some("sent") => filter.kinds.push(OperationKind::Sent),
+				Some("received") => filter.kinds.push(OperationKind::Received),
+				Some("exchanges") => filter.kinds.push(OperationKind::Exchange),
+				Some("transfers") => {
+					filter.kinds.push(OperationKind::Sent);
+					filter.kinds.push(OperationKind::Received);
+				}
+				_ => {}
+			}
+			filter.counterparty =
+				user_option(interaction, "user").map(|user| *user.id.as_u64() as i64);
+
+			// The until date is inclusive, so stop at the following midnight.
+			for (name, days) in [("since", 0), ("until", 1)] {
+				let date = match string_option(interaction, name) {
+					Some(word) => match commands::parse_local_midnight(word, timezone, days) {
+						Some(date) => Some(date),
+						None => {
+							inv.say(ctx, &format!("Please give `{}` as `YYYY-MM-DD`.", name))
+								.await;
+							return Ok(Outcome::Failed);
+						}
+					},
+					None => None,
+				};
+
+				if days == 0 {
+					filter.since = date;
+				} else {
+					filter.until = date;
+				}
+			}
+
+			let page = integer_option(interaction, "page").unwrap_or(1);
+			commands::show_statement(ctx, inv, currency, filter, timezone, page).await
+		}
+		"stat" => match string_option(interaction, "currency")
+			.and_then(|code| Currency::try_from((guild, code)).ok())
+		{
+			Some(currency) => commands::show_stat(ctx, inv, currency).await,
+			None => {
+				inv.say(ctx, "Please specify a currency.").await;
+				Ok(())
+			}
+		},
+		"create" => {
+			let target = user_option(interaction, "user").unwrap_or(&interaction.user);
+			match payment_options(interaction, guild) {
+				Ok((currency, value)) => {
+					let memo = memo_option(interaction);
+					return commands::make_deposit(ctx, inv, target, currency, value, memo).await;
+				}
+				Err(response) => {
+					inv.say(ctx, &response).await;
+					return Ok(Outcome::Failed);
+				}
+			}
+		}
+		_ => Ok(()),
+	};
+
+	result.map(|()| Outcome::Done)
+}
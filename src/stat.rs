@@ -1,72 +1,52 @@
-use sqlx::Connection;
-use sqlx::SqliteConnection;
-use std::collections::HashSet;
+use sqlx::SqlitePool;
 
 use crate::currency::{Currency, CurrencyInfo};
 
 use crate::operation;
-use crate::operation::{LedgerRow, BANK_ID};
+use crate::operation::{BANK_ID, EXCHANGE_LEG};
 
-pub async fn get_money_supply(currency: Currency) -> anyhow::Result<i64> {
-	operation::get_balance(BANK_ID, currency)
+pub async fn get_money_supply(pool: &SqlitePool, currency: Currency) -> anyhow::Result<i64> {
+	operation::get_balance(pool, BANK_ID, currency)
 		.await
 		.map(|value| -value)
 }
 
-pub async fn get_all_transfers(currency: Currency) -> anyhow::Result<i64> {
+pub async fn get_all_transfers(pool: &SqlitePool, currency: Currency) -> anyhow::Result<i64> {
 	let currency_info = CurrencyInfo::from(currency);
-	let mut conn = SqliteConnection::connect("sqlite://bank_database.db").await?;
 
-	Ok(sqlx::query_as::<_, (i64,)>(
-		r#"SELECT SUM(value) FROM Transfer
-		WHERE from_account != ? AND currency=?"#,
-	)
-	.bind(BANK_ID)
-	.bind(currency_info.code)
-	.fetch_one(&mut conn)
-	.await?
-	.0)
+	// Exchanges and reversals move money without any new activity.
+	let query = format!(
+		r#"SELECT COALESCE(SUM(value), 0) FROM Transfer
+		WHERE from_account != ? AND guild_id=? AND currency=?
+		AND reverses IS NULL AND NOT {}"#,
+		EXCHANGE_LEG
+	);
+
+	Ok(sqlx::query_as::<_, (i64,)>(&query)
+		.bind(BANK_ID)
+		.bind(currency.guild())
+		.bind(currency_info.code)
+		.fetch_one(pool)
+		.await?
+		.0)
 }
 
-pub async fn get_all_balances(currency: Currency) -> anyhow::Result<Vec<i64>> {
+pub async fn get_all_balances(pool: &SqlitePool, currency: Currency) -> anyhow::Result<Vec<i64>> {
 	let currency_info = CurrencyInfo::from(currency);
-	let mut conn = SqliteConnection::connect("sqlite://bank_database.db").await?;
 
-	let rows = sqlx::query_as::<_, LedgerRow>(
-		r#"SELECT * FROM Transfer
-		WHERE currency=?
-		ORDER BY id DESC"#,
+	Ok(sqlx::query_as::<_, (i64,)>(
+		r#"SELECT balance FROM Account
+		WHERE guild_id=? AND currency=? AND account != ?
+		ORDER BY balance"#,
 	)
+	.bind(currency.guild())
 	.bind(currency_info.code)
-	.fetch_all(&mut conn)
-	.await?;
-
-	let mut processed = HashSet::new();
-
-	let mut result = rows
-		.into_iter()
-		.fold(vec![], move |mut balances, ledger_row| {
-			let from_account = ledger_row.from_account;
-			let from_balance = ledger_row.from_balance;
-
-			let to_account = ledger_row.to_account;
-			let to_balance = ledger_row.to_balance;
-
-			if from_account != BANK_ID && !processed.contains(&from_account) {
-				balances.push(from_balance);
-				processed.insert(from_account);
-			}
-
-			if to_account != BANK_ID && !processed.contains(&to_account) {
-				balances.push(to_balance);
-				processed.insert(to_account);
-			}
-
-			balances
-		});
-
-	result.sort();
-	Ok(result)
+	.bind(BANK_ID)
+	.fetch_all(pool)
+	.await?
+	.into_iter()
+	.map(|row| row.0)
+	.collect())
 }
 
 pub struct Tendency {
@@ -75,7 +55,7 @@ pub struct Tendency {
 	pub gini: f64,
 }
 
-pub fn calc_balances(balances: &Vec<i64>) -> Tendency {
+pub fn calc_balances(balances: &[i64]) -> Tendency {
 	// Empty balances case.
 	let size = balances.len();
 	if size == 0 {
@@ -87,7 +67,7 @@ pub fn calc_balances(balances: &Vec<i64>) -> Tendency {
 	}
 
 	// Calculate median. 0 1 2 3 4
-	let median: f64 = if size % 2 == 0 {
+	let median: f64 = if size.is_multiple_of(2) {
 		(balances[size / 2] as f64 + balances[size / 2 - 1] as f64) / 2.0
 	} else {
 		balances[size / 2] as f64